@@ -0,0 +1,11 @@
+ALTER TABLE views ADD COLUMN scanned_at INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    host TEXT NOT NULL,
+    link JSON NOT NULL,
+    repo JSON,
+    scanned_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS history_host_link ON history (host, link);
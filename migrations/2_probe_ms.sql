@@ -0,0 +1,2 @@
+ALTER TABLE views ADD COLUMN probe_ms INTEGER;
+ALTER TABLE history ADD COLUMN probe_ms INTEGER;
@@ -0,0 +1,2 @@
+ALTER TABLE views ADD COLUMN failure JSON;
+ALTER TABLE history ADD COLUMN failure JSON;
@@ -0,0 +1,6 @@
+ALTER TABLE views ADD COLUMN reachable BOOLEAN;
+ALTER TABLE history ADD COLUMN reachable BOOLEAN;
+UPDATE views SET reachable = (repo != 'null')
+    WHERE json_extract(link, '$.Net') IS NOT NULL;
+UPDATE history SET reachable = (repo != 'null')
+    WHERE json_extract(link, '$.Net') IS NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE views ADD COLUMN error TEXT;
+ALTER TABLE history ADD COLUMN error TEXT;
@@ -0,0 +1,99 @@
+use std::{collections::HashSet, path::PathBuf};
+
+use serde::Serialize;
+
+use super::Paint;
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+enum Risk {
+    /// Purely local work.
+    NoRemotes,
+    /// Every remote failed to be read, from the same host.
+    RemotesUnreachable,
+}
+
+#[derive(Serialize, Debug)]
+struct AtRisk {
+    host: String,
+    link: data::Link,
+    risk: Risk,
+}
+
+impl Cmd {
+    /// Lists local repos which have no copy known to be elsewhere.
+    /// Remotes not yet probed aren't counted as unreachable.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let views = storage.views().await?;
+        let unreachable: HashSet<(&str, &str)> = views
+            .iter()
+            .filter(|view| view.reachable == Some(false))
+            .filter_map(|view| match &view.link {
+                data::Link::Net { url } => {
+                    Some((view.host.as_str(), url.as_str()))
+                }
+                data::Link::Fs { .. } => None,
+            })
+            .collect();
+        let mut at_risk = Vec::new();
+        for view in &views {
+            let (data::Link::Fs { .. }, Some(repo)) =
+                (&view.link, &view.repo)
+            else {
+                continue;
+            };
+            if repo.identity_only {
+                // Remotes unknown.
+                continue;
+            }
+            let remotes = &repo.remotes;
+            let risk = if remotes.is_empty() {
+                Risk::NoRemotes
+            } else if remotes.values().all(|url| {
+                unreachable.contains(&(view.host.as_str(), url.as_str()))
+            }) {
+                Risk::RemotesUnreachable
+            } else {
+                continue;
+            };
+            at_risk.push(AtRisk {
+                host: view.host.clone(),
+                link: view.link.clone(),
+                risk,
+            });
+        }
+        at_risk.sort_by(|a, b| {
+            (&a.host, &a.link, a.risk).cmp(&(&b.host, &b.link, b.risk))
+        });
+        match self.output {
+            super::Output::Human => {
+                for AtRisk { host, link, risk } in &at_risk {
+                    let risk = match risk {
+                        Risk::NoRemotes => Paint::Yellow.paint("no_remotes"),
+                        Risk::RemotesUnreachable => {
+                            Paint::Red.paint("remotes_unreachable")
+                        }
+                    };
+                    println!("{host} {risk} {link}");
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&at_risk)?);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use super::Paint;
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+
+    /// Database file of the earlier scan.
+    old_db_file: PathBuf,
+
+    /// Database file of the later scan.
+    new_db_file: PathBuf,
+}
+
+#[derive(Serialize, Debug)]
+struct Changed {
+    host: String,
+    link: data::Link,
+    was_readable: bool,
+    is_readable: bool,
+    diff: data::RepoDiff,
+}
+
+#[derive(Serialize, Debug, Default)]
+struct Report {
+    added: Vec<(String, data::Link)>,
+    removed: Vec<(String, data::Link)>,
+    changed: Vec<Changed>,
+}
+
+impl Report {
+    fn new(old: Views, mut new: Views) -> Self {
+        let mut report = Self::default();
+        for (key, old_view) in old {
+            match new.remove(&key) {
+                None => report.removed.push(key),
+                Some(new_view) => {
+                    let (host, link) = key;
+                    let diff = match (&old_view.repo, &new_view.repo) {
+                        (Some(old_repo), Some(new_repo)) => {
+                            old_repo.diff(new_repo)
+                        }
+                        _ => data::RepoDiff::default(),
+                    };
+                    let was_readable = old_view.repo.is_some();
+                    let is_readable = new_view.repo.is_some();
+                    if !diff.is_empty() || was_readable != is_readable {
+                        report.changed.push(Changed {
+                            host,
+                            link,
+                            was_readable,
+                            is_readable,
+                            diff,
+                        });
+                    }
+                }
+            }
+        }
+        report.added.extend(new.into_keys());
+        report
+    }
+}
+
+impl Cmd {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let old = load(&self.old_db_file).await?;
+        let new = load(&self.new_db_file).await?;
+        let report = Report::new(old, new);
+        match self.output {
+            super::Output::Human => {
+                for (host, link) in &report.added {
+                    println!(
+                        "{}",
+                        Paint::diff_line(&format!("+ {host} {link}"))
+                    );
+                }
+                for (host, link) in &report.removed {
+                    println!(
+                        "{}",
+                        Paint::diff_line(&format!("- {host} {link}"))
+                    );
+                }
+                for changed in &report.changed {
+                    println!(
+                        "{}",
+                        Paint::diff_line(&format!(
+                            "~ {} {}",
+                            changed.host, changed.link
+                        ))
+                    );
+                    match (changed.was_readable, changed.is_readable) {
+                        (true, false) => println!(
+                            "    {}",
+                            Paint::Red.paint("became unreadable")
+                        ),
+                        (false, true) => println!(
+                            "    {}",
+                            Paint::Green.paint("became readable")
+                        ),
+                        _ => {}
+                    }
+                    for line in changed.diff.to_string().lines() {
+                        println!("    {}", Paint::diff_line(line));
+                    }
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Latest views, by host and link.
+type Views = BTreeMap<(String, data::Link), data::View>;
+
+async fn load(db_file: &Path) -> anyhow::Result<Views> {
+    let storage = data::Storage::open_readonly(db_file).await?;
+    let views = storage
+        .views()
+        .await?
+        .into_iter()
+        .map(|view| ((view.host.clone(), view.link.clone()), view))
+        .collect();
+    Ok(views)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::data::tests::view;
+
+    fn with_branch(mut view: data::View, leaf: &str) -> data::View {
+        let branch = data::Branch {
+            roots: HashSet::from(["root".to_string()]),
+            leaf: leaf.to_string(),
+            committed_at: None,
+            upstream: None,
+        };
+        let repo = view.repo.as_mut().unwrap();
+        repo.branches.insert("main".to_string(), branch);
+        view
+    }
+
+    async fn db(dir: &Path, name: &str, views: &[data::View]) -> Views {
+        let file = dir.join(name);
+        let storage = data::Storage::connect(&file).await.unwrap();
+        storage.store_views(views).await.unwrap();
+        load(&file).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn added_removed_and_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut broke = view("h", "/src/broke", &[]);
+        broke.repo = None;
+        let old = db(
+            dir.path(),
+            "old.db",
+            &[
+                view("h", "/src/same", &[("origin", "https://x.org/same")]),
+                view("h", "/src/gone", &[]),
+                view("h", "/src/moved", &[("origin", "https://x.org/a")]),
+                with_branch(view("h", "/src/work", &[]), "1111"),
+                view("h", "/src/broke", &[]),
+            ],
+        )
+        .await;
+        let new = db(
+            dir.path(),
+            "new.db",
+            &[
+                view("h", "/src/same", &[("origin", "https://x.org/same")]),
+                view("h", "/src/moved", &[("origin", "https://y.org/a")]),
+                with_branch(view("h", "/src/work", &[]), "2222"),
+                broke,
+                view("h2", "/src/gone", &[]),
+            ],
+        )
+        .await;
+        let report = Report::new(old, new);
+
+        let link = |s: &str| s.parse::<data::Link>().unwrap();
+        assert_eq!(report.added, [("h2".to_string(), link("/src/gone"))]);
+        assert_eq!(report.removed, [("h".to_string(), link("/src/gone"))]);
+        let changed: Vec<(String, bool, bool, String)> = report
+            .changed
+            .iter()
+            .map(|c| {
+                (
+                    c.link.to_string(),
+                    c.was_readable,
+                    c.is_readable,
+                    c.diff.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                ("/src/broke".into(), true, false, String::new()),
+                ("/src/moved".into(), true, true, "~ remote origin\n".into()),
+                ("/src/work".into(), true, true, "~ branch main\n".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail};
+use serde::Serialize;
+
+use super::Paint;
+use crate::{data, os};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file to be scanned into.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+
+    /// Also check that this SSH destination, e.g. "user@host", can be
+    /// reached without prompting, as `find --ssh` and SSH remotes need.
+    #[clap(long)]
+    ssh: Option<String>,
+
+    /// Least free space, in bytes, to expect in the temporary dir, where
+    /// remotes are cloned.
+    #[clap(long, default_value_t = 1024 * 1024 * 1024)]
+    min_temp_free: u64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Pass,
+    Fail,
+    /// Not applicable, e.g. without a destination to check.
+    Skip,
+}
+
+#[derive(Serialize, Debug)]
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// How to fix it, if failed.
+    hint: Option<&'static str>,
+}
+
+impl Check {
+    fn new(
+        name: &'static str,
+        result: anyhow::Result<String>,
+        hint: &'static str,
+    ) -> Self {
+        match result {
+            Ok(detail) => Self {
+                name,
+                status: Status::Pass,
+                detail,
+                hint: None,
+            },
+            Err(error) => Self {
+                name,
+                status: Status::Fail,
+                detail: format!("{error:#}"),
+                hint: Some(hint),
+            },
+        }
+    }
+}
+
+impl Cmd {
+    /// Checks what scans need, so setup problems show up before one
+    /// starts, rather than half-way through. Fails if any check does.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let temp_dir = std::env::temp_dir();
+        let mut checks = vec![
+            Check::new(
+                "git",
+                git_version().await,
+                "Install git and make sure it's on the PATH.",
+            ),
+            Check::new(
+                "db_writable",
+                db_writable(&self.db_file).await,
+                "Choose a --db-file in a dir you can write to.",
+            ),
+            Check::new(
+                "temp_free",
+                temp_free(&temp_dir, self.min_temp_free).await,
+                "Free up space, or point TMPDIR elsewhere, or clone remotes \
+                into --clone-tempdir.",
+            ),
+        ];
+        checks.push(match &self.ssh {
+            Some(dest) => Check::new(
+                "ssh_batch_mode",
+                ssh_batch_mode(dest).await,
+                "Set up key-based auth, e.g. with ssh-agent, and accept the \
+                host key, so ssh doesn't prompt.",
+            ),
+            None => Check {
+                name: "ssh_batch_mode",
+                status: Status::Skip,
+                detail: "No --ssh destination given.".to_string(),
+                hint: None,
+            },
+        });
+        match self.output {
+            super::Output::Human => {
+                for Check {
+                    name,
+                    status,
+                    detail,
+                    hint,
+                } in &checks
+                {
+                    let status = match status {
+                        Status::Pass => Paint::Green.paint("pass"),
+                        Status::Fail => Paint::Red.paint("fail"),
+                        Status::Skip => Paint::Yellow.paint("skip"),
+                    };
+                    println!("{status} {name:<14} {detail}");
+                    if let Some(hint) = hint {
+                        println!("     {:<14} {hint}", "");
+                    }
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&checks)?);
+            }
+        }
+        let failed = checks
+            .iter()
+            .filter(|check| check.status == Status::Fail)
+            .count();
+        if failed > 0 {
+            bail!("Failed {failed} checks.");
+        }
+        Ok(())
+    }
+}
+
+async fn git_version() -> anyhow::Result<String> {
+    let out = os::cmd("git", &["--version"]).await?;
+    Ok(String::from_utf8_lossy(&out).trim().to_string())
+}
+
+/// Creates, migrates and stores into a scratch database next to the
+/// given one, which is left untouched, but for checking it can be opened
+/// for writing if it exists.
+async fn db_writable(db_file: &Path) -> anyhow::Result<String> {
+    let dir = match db_file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    if db_file.try_exists()? {
+        std::fs::OpenOptions::new().write(true).open(db_file)?;
+    }
+    let scratch = tempfile::Builder::new()
+        .prefix(".git-tracker-doctor")
+        .suffix(".db")
+        .tempfile_in(&dir)?;
+    let storage = data::Storage::connect_force(scratch.path()).await?;
+    storage.store_views(&[]).await?;
+    Ok(format!("Can write databases in {dir:?}."))
+}
+
+/// Free space, as reported by `df`, since std has no portable way to ask.
+async fn temp_free(dir: &Path, min: u64) -> anyhow::Result<String> {
+    let out = os::cmd("df", &[OsStr::new("-Pk"), dir.as_os_str()]).await?;
+    let out = String::from_utf8_lossy(&out);
+    let free_kib: u64 = out
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .ok_or_else(|| anyhow!("Unexpected df output: {out:?}"))?
+        .parse()?;
+    let free = free_kib * 1024;
+    if free < min {
+        bail!("Only {free} bytes free in {dir:?}, below {min}.");
+    }
+    Ok(format!("{free} bytes free in {dir:?}."))
+}
+
+async fn ssh_batch_mode(dest: &str) -> anyhow::Result<String> {
+    os::cmd(
+        "ssh",
+        &["-oBatchMode=yes", "-oConnectTimeout=10", dest, "--", "true"],
+    )
+    .await?;
+    Ok(format!("Reached {dest:?} without prompting."))
+}
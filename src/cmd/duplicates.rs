@@ -0,0 +1,165 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::{data, git};
+
+/// What makes two local repos copies of the same project.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default)]
+pub enum Identity {
+    /// Sharing a root commit. Holds however remotes are named or moved,
+    /// but not once history is rewritten, e.g. by filter-branch or
+    /// squashing, and needs roots, which shallow clones lack.
+    #[default]
+    Roots,
+    /// Sharing a remote URL, once normalized. Holds however histories
+    /// diverge, but misses repos without remotes, or mirroring the same
+    /// project from different addresses, and matches unrelated repos
+    /// pushing to one shared remote.
+    Remotes,
+    /// Either, so misses the fewest copies, at the cost of the false
+    /// matches of both.
+    Both,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+
+    /// How to tell that repos are copies of each other.
+    #[clap(long, value_enum, default_value_t = Identity::Roots)]
+    identity: Identity,
+}
+
+#[derive(Serialize, Debug)]
+struct Member<'a> {
+    host: &'a str,
+    link: &'a data::Link,
+}
+
+impl Cmd {
+    /// Groups the stored local repos which are copies of each other, across
+    /// all hosts, transitively: A and C are grouped if both match B.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let views: Vec<data::View> = storage
+            .views()
+            .await?
+            .into_iter()
+            .filter(|view| {
+                matches!(view.link, data::Link::Fs { .. })
+                    && view.repo.is_some()
+            })
+            .collect();
+        let mut groups = UnionFind::new(views.len());
+        // First repo with each key, to join later ones with.
+        let mut firsts: HashMap<String, usize> = HashMap::new();
+        for (i, view) in views.iter().enumerate() {
+            let Some(repo) = &view.repo else { continue };
+            for key in keys(repo, self.identity) {
+                let first = *firsts.entry(key).or_insert(i);
+                groups.join(first, i);
+            }
+        }
+        let mut duplicates: HashMap<usize, Vec<Member>> = HashMap::new();
+        for (i, view) in views.iter().enumerate() {
+            duplicates.entry(groups.find(i)).or_default().push(Member {
+                host: &view.host,
+                link: &view.link,
+            });
+        }
+        let mut duplicates: Vec<Vec<Member>> = duplicates
+            .into_values()
+            .filter(|copies| copies.len() > 1)
+            .collect();
+        for copies in &mut duplicates {
+            copies.sort_by(|a, b| (a.host, a.link).cmp(&(b.host, b.link)));
+        }
+        duplicates.sort_by(|a, b| {
+            (a[0].host, a[0].link).cmp(&(b[0].host, b[0].link))
+        });
+        match self.output {
+            super::Output::Human => {
+                for (i, copies) in duplicates.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    for Member { host, link } in copies {
+                        println!("{host} {link}");
+                    }
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&duplicates)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keys any one of which, shared, makes repos copies, prefixed by kind
+/// so roots and URLs can't collide.
+/// Roots of shallow repos aren't real, so they aren't keys.
+fn keys(repo: &data::Repo, identity: Identity) -> Vec<String> {
+    let roots = repo
+        .roots
+        .iter()
+        .chain(repo.branches.values().flat_map(|b| b.roots.iter()))
+        .filter(|_| !repo.shallow)
+        .map(|root| format!("root:{root}"));
+    let remotes = repo
+        .remotes
+        .values()
+        .map(|url| format!("remote:{}", normalize_url(url)));
+    match identity {
+        Identity::Roots => roots.collect(),
+        Identity::Remotes => remotes.collect(),
+        Identity::Both => roots.chain(remotes).collect(),
+    }
+}
+
+/// Reduces the many ways of addressing a remote to one, e.g.
+/// "git@github.com:me/repo.git" and "https://github.com/me/repo" both to
+/// "github.com/me/repo", ignoring protocol, user, port and `.git` suffix.
+fn normalize_url(url: &str) -> String {
+    let data::RemoteUrl {
+        host, owner, name, ..
+    } = git::parse_remote_url(url);
+    let host = host.unwrap_or_default();
+    match owner {
+        Some(owner) => format!("{host}/{owner}/{name}"),
+        None => format!("{host}/{name}"),
+    }
+}
+
+struct UnionFind(Vec<usize>);
+
+impl UnionFind {
+    fn new(n: usize) -> Self {
+        Self((0..n).collect())
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.0[root] != root {
+            root = self.0[root];
+        }
+        // Point the whole path at the root, so later finds are quick.
+        let mut i = i;
+        while self.0[i] != root {
+            i = std::mem::replace(&mut self.0[i], root);
+        }
+        root
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        self.0[b] = a;
+    }
+}
@@ -0,0 +1,132 @@
+use std::{fmt::Write as _, path::PathBuf};
+
+use futures::{stream::BoxStream, StreamExt};
+use tokio::io::{self, AsyncWrite, AsyncWriteExt};
+
+use crate::data;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default)]
+pub enum Format {
+    /// A single JSON array.
+    Json,
+
+    /// One JSON object per line.
+    #[default]
+    Ndjson,
+
+    /// Remotes of each local repo as git config sections, under a comment
+    /// naming the repo, ready to paste into its config:
+    ///
+    /// ```text
+    /// # host:/path/to/repo/.git
+    /// [remote "origin"]
+    ///     url = https://example.com/repo.git
+    /// ```
+    ///
+    /// Remotes themselves and local repos without any are left out.
+    GitConfig,
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short = 'F', long, value_enum, default_value_t = Format::Ndjson)]
+    format: Format,
+
+    /// Export every stored scan rather than just the latest view of each
+    /// link. Importing this reproduces the history as well.
+    #[clap(long, default_value_t = false)]
+    history: bool,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let views = if self.history {
+            storage.history_stream()
+        } else {
+            storage.views_stream()
+        };
+        let mut out = io::BufWriter::new(io::stdout());
+        let count = write_views(self.format, views, &mut out).await?;
+        out.flush().await?;
+        tracing::info!(count, "Export done.");
+        Ok(())
+    }
+}
+
+/// Writes the views in the format, returning how many.
+pub(super) async fn write_views<W: AsyncWrite + Unpin>(
+    format: Format,
+    mut views: BoxStream<'_, anyhow::Result<data::View>>,
+    out: &mut W,
+) -> anyhow::Result<usize> {
+    let mut count: usize = 0;
+    if let Format::Json = format {
+        out.write_all(b"[").await?;
+    }
+    while let Some(view) = views.next().await {
+        let view = view?;
+        match format {
+            Format::Json => {
+                if count > 0 {
+                    out.write_all(b",").await?;
+                }
+                out.write_all(b"\n").await?;
+                out.write_all(&serde_json::to_vec(&view)?).await?;
+            }
+            Format::Ndjson => {
+                out.write_all(&serde_json::to_vec(&view)?).await?;
+                out.write_all(b"\n").await?;
+            }
+            Format::GitConfig => {
+                out.write_all(git_config(&view).as_bytes()).await?;
+            }
+        }
+        count += 1;
+    }
+    if let Format::Json = format {
+        out.write_all(b"\n]\n").await?;
+    }
+    Ok(count)
+}
+
+/// The view in [`Format::GitConfig`], or nothing if it has no remotes.
+fn git_config(view: &data::View) -> String {
+    let mut text = String::new();
+    let (data::Link::Fs { dir }, Some(repo)) = (&view.link, &view.repo)
+    else {
+        return text;
+    };
+    if repo.remotes.is_empty() {
+        return text;
+    }
+    let mut remotes: Vec<_> = repo.remotes.iter().collect();
+    remotes.sort();
+    let _ = writeln!(text, "# {}:{}", view.host, dir.display());
+    for (name, url) in remotes {
+        let _ = writeln!(text, "[remote {}]", quote(name));
+        let _ = writeln!(text, "\turl = {}", config_value(url));
+    }
+    text.push('\n');
+    text
+}
+
+/// Quoted, as git requires of subsection names.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quoted only if git would otherwise read it differently, e.g. cut
+/// short at a comment character.
+fn config_value(s: &str) -> String {
+    if s.contains(['#', ';', '"', '\\']) || s.trim() != s {
+        quote(s)
+    } else {
+        s.to_string()
+    }
+}
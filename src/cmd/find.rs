@@ -1,19 +1,66 @@
-use std::{collections::HashSet, path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Write as _},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
-use dashmap::DashSet;
+use dashmap::{DashMap, DashSet};
 use futures::{stream, StreamExt};
-use tokio::sync::mpsc;
+use serde::Serialize;
+use tokio::sync::{mpsc, Notify, Semaphore, SemaphorePermit};
 use tokio_stream::wrappers::UnboundedReceiverStream;
 use tracing::{info_span, Instrument};
 
-use crate::{data, git, os};
+use super::Paint;
+use crate::{data, git, manifest, os, redact};
+
+/// Which local repos to keep, by whether they have a work tree.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepoKind {
+    #[default]
+    Any,
+    /// Without a work tree, e.g. mirrors on a server.
+    Bare,
+    /// With a work tree, e.g. checkouts on a developer's machine.
+    Worktree,
+}
+
+impl RepoKind {
+    /// Repos which can't be told apart are kept only for `Any`.
+    async fn matches(self, run: &git::RunOpts, dir: &Path) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Bare => git::is_bare(run, dir).await.is_ok_and(|bare| bare),
+            Self::Worktree => {
+                git::is_bare(run, dir).await.is_ok_and(|bare| !bare)
+            }
+        }
+    }
+}
 
 #[derive(clap::Args, Debug, Clone)]
 pub struct Cmd {
-    /// Database file.
-    #[clap(short, long, default_value = "git-tracker.db")]
-    db_file: PathBuf,
+    /// Database file. Defaults to "git-tracker.db", unless --db-dir is
+    /// given.
+    #[clap(short, long)]
+    db_file: Option<PathBuf>,
+
+    /// Directory of per-host databases, to use the one named after this
+    /// host, "<host>.db", as the database file.
+    #[clap(long, conflicts_with = "db_file")]
+    db_dir: Option<PathBuf>,
+
+    /// Use the database file even if it doesn't look like one of ours,
+    /// adding our tables to it.
+    #[clap(long, default_value_t = false)]
+    force: bool,
 
     /// Database insertion batch size - how may views to pack into a single transaction.
     #[clap(short, long, default_value_t = 1000)]
@@ -23,6 +70,303 @@ pub struct Cmd {
     #[clap(short, long, default_value_t = false)]
     follow: bool,
 
+    /// Let git prompt for credentials when probing remotes. Only for
+    /// attended runs: the scan blocks until each prompt is answered.
+    #[clap(long, default_value_t = false)]
+    allow_auth: bool,
+
+    /// Git credential helper to use when probing remotes, e.g. "store".
+    #[clap(long)]
+    credential_helper: Option<String>,
+
+    /// File containing an access token to answer HTTPS password prompts
+    /// with when probing remotes.
+    #[clap(long)]
+    token_file: Option<PathBuf>,
+
+    /// Username to pair with the token from --token-file.
+    #[clap(long, default_value = "x-access-token")]
+    token_user: String,
+
+    /// Store views under this host name rather than looking it up, e.g.
+    /// to keep it stable across environments.
+    #[clap(long)]
+    host_name: Option<String>,
+
+    /// How to spell the looked up host name.
+    #[clap(long, value_enum, default_value_t = super::HostStyle::Raw)]
+    host_style: super::HostStyle,
+
+    /// Keep bare clones of remotes in this directory and fetch into them
+    /// on later runs, instead of cloning each remote afresh every time.
+    #[clap(long)]
+    remote_cache: Option<PathBuf>,
+
+    /// Make the temporary clones of remotes in this directory rather than
+    /// the system's temp dir, which is often too small a tmpfs for them.
+    #[clap(long, conflicts_with = "remote_cache")]
+    clone_tempdir: Option<PathBuf>,
+
+    /// Clone remotes only this many commits deep. Faster, but remote
+    /// branches are then stored without roots, since shallow clones
+    /// can't reach them.
+    #[clap(long)]
+    remote_depth: Option<u32>,
+
+    /// Proxy to probe HTTP(S) remotes through, overriding the standard
+    /// HTTP_PROXY and HTTPS_PROXY env vars, which are otherwise used.
+    #[clap(long)]
+    http_proxy: Option<redact::Url>,
+
+    /// Proxy to probe remotes through, e.g. "socks5://host:1080", as in
+    /// the ALL_PROXY env var. Doesn't apply to SSH remotes.
+    #[clap(long)]
+    all_proxy: Option<redact::Url>,
+
+    /// Git config setting, as "key=value", to run every git command with,
+    /// as with `git -c`. Can be repeated, e.g. for
+    /// "url.https://github.com/.insteadOf=git://github.com/".
+    #[clap(long)]
+    git_config: Vec<git::ConfigOverride>,
+
+    /// SSH command to reach SSH remotes with, as in GIT_SSH_COMMAND, e.g.
+    /// "ssh -i ~/.ssh/scan_key".
+    #[clap(long)]
+    remote_ssh_command: Option<String>,
+
+    /// Seconds to wait for a connection to an SSH remote before giving
+    /// up on it.
+    #[clap(long, default_value_t = 10)]
+    ssh_connect_timeout: u64,
+
+    /// Print a summary of the scan in this format.
+    #[clap(short, long, value_enum)]
+    output: Option<super::Output>,
+
+    /// Print the git dir of each local repo as it's found.
+    #[clap(long, default_value_t = false)]
+    print_paths: bool,
+
+    /// Separate printed paths with NUL rather than newline, as for
+    /// `xargs -0`.
+    #[clap(
+        short = '0',
+        long,
+        default_value_t = false,
+        requires = "print_paths"
+    )]
+    print0: bool,
+
+    /// Run this shell command on each local repo stored, with `{}` replaced
+    /// by its git dir, quoted, e.g. `git -C {} fetch`. Over SSH, runs there.
+    #[clap(long)]
+    exec: Option<String>,
+
+    /// Write Prometheus text-format metrics about the scan to this file,
+    /// e.g. for the node_exporter textfile collector.
+    #[clap(long)]
+    metrics_file: Option<PathBuf>,
+
+    /// Write a manifest of the local repos found, with their remotes and
+    /// roots, to this file, as JSON in a stable shape for backup tools.
+    #[clap(long)]
+    manifest_file: Option<PathBuf>,
+
+    /// Append each view to this file as a line of JSON as soon as it's
+    /// read, rather than in batches, as the database is written. Gives a
+    /// live log of the scan, which survives even if the database doesn't.
+    #[clap(long)]
+    jsonl_out: Option<PathBuf>,
+
+    /// Stop the whole scan once a remote turns out to require
+    /// authentication, which means the given credentials are wrong, rather
+    /// than going on to fail on every other remote too. Views read by then
+    /// are still stored.
+    #[clap(long, default_value_t = false)]
+    fail_fast_on_auth: bool,
+
+    /// Only count the local repos found, without reading or storing them,
+    /// to quickly size up a tree before a full scan. The count is printed
+    /// as a bare number, or as JSON with --output json.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["checkpoint_file", "sample"]
+    )]
+    count_only: bool,
+
+    /// Stop probing remotes once their clones add up to this many bytes,
+    /// e.g. on a metered connection. Remotes are then probed one at a
+    /// time, so only the last clone can go over. Remotes left unprobed
+    /// aren't stored, and the summary reports the scan as truncated.
+    #[clap(long, conflicts_with = "no_remotes")]
+    max_download_bytes: Option<u64>,
+
+    /// Don't probe the remotes of found repos, for a quick or offline
+    /// inventory. Their addresses are still recorded.
+    #[clap(long, default_value_t = false)]
+    no_remotes: bool,
+
+    /// Record only the root commits of each local repo, which identify
+    /// it, e.g. to find forks in enormous scans. Leaves out branches,
+    /// remotes and descriptions, so no remotes are probed either.
+    #[clap(long, default_value_t = false)]
+    identity_only: bool,
+
+    /// Keep only local repos with a remote URL containing this text, e.g.
+    /// "github.com". Others are neither stored nor have their remotes
+    /// probed. Can be repeated to accept any of several.
+    #[clap(long, conflicts_with = "identity_only")]
+    require_remote: Vec<String>,
+
+    /// Keep only local repos with at least one remote.
+    #[clap(long, default_value_t = false, conflicts_with = "identity_only")]
+    only_with_remotes: bool,
+
+    /// Keep only local repos with at least one branch, leaving out e.g.
+    /// freshly initialized ones.
+    #[clap(long, default_value_t = false, conflicts_with = "identity_only")]
+    only_with_branches: bool,
+
+    /// Keep only local repos of this kind.
+    #[clap(long, value_enum, default_value_t = RepoKind::Any)]
+    repo_kind: RepoKind,
+
+    /// Store paths of local repos relative to this dir, so the database
+    /// stays meaningful when shared or the tree is moved. Repos outside
+    /// of it are still stored with absolute paths.
+    #[clap(long)]
+    relative_to: Option<PathBuf>,
+
+    /// Record in this file each subtree of the search paths once it's
+    /// been scanned, and skip recorded ones, so an interrupted scan can
+    /// be resumed. Subtrees are then scanned one at a time. The file is
+    /// removed once a scan completes.
+    #[clap(long, conflicts_with = "resolve")]
+    checkpoint_file: Option<PathBuf>,
+
+    /// Print to stderr how long was spent in each phase of the scan, e.g.
+    /// walking dirs, reading branches or cloning remotes. Times of
+    /// concurrent phases add up, so can exceed the scan's duration.
+    #[clap(long, default_value_t = false)]
+    profile: bool,
+
+    /// Stop looking for more repos after this many seconds, so the scan
+    /// fits in a time budget. Repos found by then are still read and
+    /// stored, and the summary reports the scan as truncated.
+    #[clap(long)]
+    deadline: Option<u64>,
+
+    /// At the deadline, also stop reading repos already found, rather
+    /// than letting them finish.
+    #[clap(long, default_value_t = false, requires = "deadline")]
+    deadline_cancel: bool,
+
+    /// Stop looking for more repos once this many local ones are found,
+    /// e.g. for a quick look at a huge file system. They're still read and
+    /// stored, along with their remotes, and the summary reports the scan
+    /// as truncated.
+    #[clap(long)]
+    max_total_repos: Option<usize>,
+
+    /// Name of the dirs to look for as git dirs. Can be repeated, and
+    /// may contain `*` and `?` wildcards, e.g. "*.git" for bare repos.
+    /// Dirs not named ".git" are only taken for bare repos.
+    #[clap(long = "git-dir-name", default_value = ".git")]
+    git_dir_names: Vec<String>,
+
+    /// Don't store views of local repos which could not be read. Unlike
+    /// for remotes, where that means unreachable and is always stored,
+    /// these are mostly noise, e.g. broken or half-cloned repos.
+    #[clap(long, default_value_t = false)]
+    no_store_empty_repos: bool,
+
+    /// Don't walk the rest of a work tree once its `.git` dir is found.
+    /// Much faster on big source trees, but misses nested repos, such as
+    /// submodules.
+    #[clap(long, default_value_t = false)]
+    exclude_git_dir_children: bool,
+
+    /// Log each path skipped by --ignore-paths or
+    /// --exclude-git-dir-children while searching, at info level, e.g. to
+    /// find out why a repo was missed.
+    #[clap(long, default_value_t = false)]
+    print_ignored: bool,
+
+    /// Only read this many of the dirs found, picked at random once the
+    /// search is done, e.g. to quickly try out options on a huge tree.
+    #[clap(long, conflicts_with_all = ["resolve", "checkpoint_file"])]
+    sample: Option<usize>,
+
+    /// Seed for picking the --sample, to pick the same dirs again. Defaults
+    /// to the current time, and is logged either way.
+    #[clap(long, requires = "sample")]
+    sample_seed: Option<u64>,
+
+    /// Only read the roots of local repos whose objects take up more than
+    /// this many bytes, since reading the rest of a giant repo can take
+    /// minutes. They're marked as skipped and counted in the summary.
+    #[clap(long, conflicts_with = "identity_only")]
+    max_repo_size: Option<u64>,
+
+    /// Rather than searching under the given paths, let git find the repo
+    /// containing each of them, as it would when run from there.
+    #[clap(long, default_value_t = false)]
+    resolve: bool,
+
+    /// Take each search path to be a repo, and read it without walking
+    /// under it, e.g. when the list of repos is already known. Those which
+    /// aren't, themselves, are walked as usual.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = [
+            "resolve", "ssh", "from_archive", "checkpoint_file", "sample",
+        ]
+    )]
+    repos_are_roots: bool,
+
+    /// Whether git dir names match regardless of case.
+    #[clap(long, value_enum, default_value_t = super::NameCase::Auto)]
+    name_case: super::NameCase,
+
+    /// Also read the git dirs of submodules, kept under `.git/modules` of
+    /// the repos found, as repos of their own. The walk doesn't descend
+    /// into git dirs, so otherwise they're left out.
+    #[clap(long, default_value_t = false)]
+    submodule_git_dirs: bool,
+
+    /// Skip local repos which already have a view stored from this host,
+    /// without reading them, e.g. to only add newly created ones. Unlike
+    /// rescanning, changes to known repos are missed.
+    #[clap(long, default_value_t = false)]
+    skip_known: bool,
+
+    /// Scan the search paths on this machine instead, e.g. "user@host",
+    /// running `find` and `git` there over SSH, which must not prompt.
+    /// Views are stored under its hostname. Remotes are not probed, and
+    /// file-only details, such as descriptions, are left out.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "resolve", "checkpoint_file", "relative_to", "sample",
+        ]
+    )]
+    ssh: Option<String>,
+
+    /// Take the search paths to be tar archives, and read the repos in
+    /// them, without extracting more than their git dirs, to a temporary
+    /// dir. Stored under the archive path joined with the git dir's path
+    /// within it. Needs GNU or BSD `tar`.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = [
+            "resolve", "ssh", "exec", "checkpoint_file", "sample",
+        ]
+    )]
+    from_archive: bool,
+
     // TODO These should actualy be regexp patterns to filter candidate paths.
     /// Ignore this path when searching for repos.
     #[clap(short, long)]
@@ -32,102 +376,870 @@ pub struct Cmd {
     search_paths: Vec<PathBuf>,
 }
 
+/// Options of a scan, as given to [`Cmd`] on the command line, but without
+/// clap, to run one from a library. Each field is the flag of the same
+/// name, e.g. `no_remotes` for `--no-remotes`, and defaults to the same,
+/// except for `store_empty_repos`, which is `--no-store-empty-repos`
+/// negated. Unlike on the command line, conflicting options are not
+/// rejected.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// use git_tracker::cmd::find::FindOptions;
+///
+/// let opts = FindOptions {
+///     search_paths: vec!["/home/me/src".into()],
+///     db_file: Some("src.db".into()),
+///     no_remotes: true,
+///     ..FindOptions::default()
+/// };
+/// opts.run().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindOptions {
+    pub db_file: Option<PathBuf>,
+    pub db_dir: Option<PathBuf>,
+    pub force: bool,
+    pub batch_size: usize,
+    pub follow: bool,
+    pub allow_auth: bool,
+    pub credential_helper: Option<String>,
+    pub token_file: Option<PathBuf>,
+    pub token_user: String,
+    pub host_name: Option<String>,
+    pub host_style: super::HostStyle,
+    pub remote_cache: Option<PathBuf>,
+    pub clone_tempdir: Option<PathBuf>,
+    pub remote_depth: Option<u32>,
+    pub http_proxy: Option<redact::Url>,
+    pub all_proxy: Option<redact::Url>,
+    pub git_config: Vec<git::ConfigOverride>,
+    pub remote_ssh_command: Option<String>,
+    pub ssh_connect_timeout: u64,
+    pub output: Option<super::Output>,
+    pub print_paths: bool,
+    pub print0: bool,
+    pub exec: Option<String>,
+    pub metrics_file: Option<PathBuf>,
+    pub manifest_file: Option<PathBuf>,
+    pub jsonl_out: Option<PathBuf>,
+    pub fail_fast_on_auth: bool,
+    pub count_only: bool,
+    pub max_download_bytes: Option<u64>,
+    pub no_remotes: bool,
+    pub identity_only: bool,
+    pub require_remote: Vec<String>,
+    pub only_with_remotes: bool,
+    pub only_with_branches: bool,
+    pub repo_kind: RepoKind,
+    pub relative_to: Option<PathBuf>,
+    pub checkpoint_file: Option<PathBuf>,
+    pub profile: bool,
+    pub deadline: Option<u64>,
+    pub deadline_cancel: bool,
+    pub max_total_repos: Option<usize>,
+    pub git_dir_names: Vec<String>,
+    pub store_empty_repos: bool,
+    pub exclude_git_dir_children: bool,
+    pub print_ignored: bool,
+    pub sample: Option<usize>,
+    pub sample_seed: Option<u64>,
+    pub max_repo_size: Option<u64>,
+    pub resolve: bool,
+    pub repos_are_roots: bool,
+    pub name_case: super::NameCase,
+    pub submodule_git_dirs: bool,
+    pub skip_known: bool,
+    pub ssh: Option<String>,
+    pub from_archive: bool,
+    pub ignore_paths: Vec<PathBuf>,
+    pub search_paths: Vec<PathBuf>,
+}
+
+impl Default for FindOptions {
+    fn default() -> Self {
+        Self {
+            db_file: None,
+            db_dir: None,
+            force: false,
+            batch_size: 1000,
+            follow: false,
+            allow_auth: false,
+            credential_helper: None,
+            token_file: None,
+            token_user: "x-access-token".to_string(),
+            host_name: None,
+            host_style: super::HostStyle::Raw,
+            remote_cache: None,
+            clone_tempdir: None,
+            remote_depth: None,
+            http_proxy: None,
+            all_proxy: None,
+            git_config: Vec::new(),
+            remote_ssh_command: None,
+            ssh_connect_timeout: 10,
+            output: None,
+            print_paths: false,
+            print0: false,
+            exec: None,
+            metrics_file: None,
+            manifest_file: None,
+            jsonl_out: None,
+            fail_fast_on_auth: false,
+            count_only: false,
+            max_download_bytes: None,
+            no_remotes: false,
+            identity_only: false,
+            require_remote: Vec::new(),
+            only_with_remotes: false,
+            only_with_branches: false,
+            repo_kind: RepoKind::Any,
+            relative_to: None,
+            checkpoint_file: None,
+            profile: false,
+            deadline: None,
+            deadline_cancel: false,
+            max_total_repos: None,
+            git_dir_names: vec![".git".to_string()],
+            store_empty_repos: true,
+            exclude_git_dir_children: false,
+            print_ignored: false,
+            sample: None,
+            sample_seed: None,
+            max_repo_size: None,
+            resolve: false,
+            repos_are_roots: false,
+            name_case: super::NameCase::Auto,
+            submodule_git_dirs: false,
+            skip_known: false,
+            ssh: None,
+            from_archive: false,
+            ignore_paths: Vec::new(),
+            search_paths: Vec::new(),
+        }
+    }
+}
+
+impl From<&Cmd> for FindOptions {
+    fn from(cmd: &Cmd) -> Self {
+        Self {
+            db_file: cmd.db_file.clone(),
+            db_dir: cmd.db_dir.clone(),
+            force: cmd.force,
+            batch_size: cmd.batch_size,
+            follow: cmd.follow,
+            allow_auth: cmd.allow_auth,
+            credential_helper: cmd.credential_helper.clone(),
+            token_file: cmd.token_file.clone(),
+            token_user: cmd.token_user.clone(),
+            host_name: cmd.host_name.clone(),
+            host_style: cmd.host_style,
+            remote_cache: cmd.remote_cache.clone(),
+            clone_tempdir: cmd.clone_tempdir.clone(),
+            remote_depth: cmd.remote_depth,
+            http_proxy: cmd.http_proxy.clone(),
+            all_proxy: cmd.all_proxy.clone(),
+            git_config: cmd.git_config.clone(),
+            remote_ssh_command: cmd.remote_ssh_command.clone(),
+            ssh_connect_timeout: cmd.ssh_connect_timeout,
+            output: cmd.output,
+            print_paths: cmd.print_paths,
+            print0: cmd.print0,
+            exec: cmd.exec.clone(),
+            metrics_file: cmd.metrics_file.clone(),
+            manifest_file: cmd.manifest_file.clone(),
+            jsonl_out: cmd.jsonl_out.clone(),
+            fail_fast_on_auth: cmd.fail_fast_on_auth,
+            count_only: cmd.count_only,
+            max_download_bytes: cmd.max_download_bytes,
+            no_remotes: cmd.no_remotes,
+            identity_only: cmd.identity_only,
+            require_remote: cmd.require_remote.clone(),
+            only_with_remotes: cmd.only_with_remotes,
+            only_with_branches: cmd.only_with_branches,
+            repo_kind: cmd.repo_kind,
+            relative_to: cmd.relative_to.clone(),
+            checkpoint_file: cmd.checkpoint_file.clone(),
+            profile: cmd.profile,
+            deadline: cmd.deadline,
+            deadline_cancel: cmd.deadline_cancel,
+            max_total_repos: cmd.max_total_repos,
+            git_dir_names: cmd.git_dir_names.clone(),
+            store_empty_repos: !cmd.no_store_empty_repos,
+            exclude_git_dir_children: cmd.exclude_git_dir_children,
+            print_ignored: cmd.print_ignored,
+            sample: cmd.sample,
+            sample_seed: cmd.sample_seed,
+            max_repo_size: cmd.max_repo_size,
+            resolve: cmd.resolve,
+            repos_are_roots: cmd.repos_are_roots,
+            name_case: cmd.name_case,
+            submodule_git_dirs: cmd.submodule_git_dirs,
+            skip_known: cmd.skip_known,
+            ssh: cmd.ssh.clone(),
+            from_archive: cmd.from_archive,
+            ignore_paths: cmd.ignore_paths.clone(),
+            search_paths: cmd.search_paths.clone(),
+        }
+    }
+}
+
 impl Cmd {
     pub async fn run(&self) -> anyhow::Result<()> {
+        FindOptions::from(self).run().await
+    }
+}
+
+impl FindOptions {
+    /// Scans and stores, as the `find` command does.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        if self.search_paths.is_empty() {
+            anyhow::bail!(
+                "No search paths given. Provide at least one path to look \
+                for repos in, e.g. `find ~/src`."
+            );
+        }
+        let started = Instant::now();
+        if self.profile {
+            crate::profile::enable();
+        }
+        let ignore_case = self.name_case.ignore_case();
+        let deadline = self
+            .deadline
+            .map(|secs| started + Duration::from_secs(secs));
+        let truncated = Arc::new(AtomicBool::new(false));
         let ignore_paths: HashSet<PathBuf> =
             self.ignore_paths.iter().cloned().collect();
+        let target = match &self.ssh {
+            Some(dest) => os::Target::Ssh(dest.clone()),
+            None => os::Target::Local,
+        };
+        let run_opts = git::RunOpts {
+            target: target.clone(),
+            config: self.git_config.clone(),
+        };
         let mut search_paths = Vec::new();
-        for path in &self.search_paths {
-            let path = path
-                .canonicalize()
-                .context(format!("Invalid local path={path:?}"))?;
-            search_paths.push(path);
+        if self.ssh.is_some() {
+            // Not ours to canonicalize, so taken as given.
+            search_paths.clone_from(&self.search_paths);
+        } else {
+            for path in &self.search_paths {
+                let path = path
+                    .canonicalize()
+                    .context(format!("Invalid local path={path:?}"))?;
+                search_paths.push(path);
+            }
+        }
+        let relative_to = match &self.relative_to {
+            Some(base) => Some(
+                base.canonicalize()
+                    .context(format!("Invalid base path={base:?}"))?,
+            ),
+            None => None,
+        };
+        // XXX Must outlive the workers, since it deletes the dir on drop.
+        let extracted = if self.from_archive {
+            Some(tempfile::tempdir()?)
+        } else {
+            None
+        };
+        // Where each extracted git dir came from, to store it as.
+        let mut archived: HashMap<PathBuf, PathBuf> = HashMap::new();
+        let resolved = if let Some(extracted) = &extracted {
+            let mut dirs = Vec::new();
+            for (i, archive) in search_paths.iter().enumerate() {
+                let dst = extracted.path().join(i.to_string());
+                tokio::fs::create_dir(&dst).await?;
+                let extract = async {
+                    let members = crate::fs::archive_git_dirs(
+                        archive,
+                        &self.git_dir_names,
+                        ignore_case,
+                    )
+                    .await?;
+                    crate::fs::extract_from_archive(archive, &members, &dst)
+                        .await?;
+                    Ok::<_, anyhow::Error>(members)
+                };
+                match extract.await {
+                    Ok(members) => {
+                        for member in members {
+                            let dir = dst.join(&member);
+                            let member =
+                                member.strip_prefix(".").unwrap_or(&member);
+                            archived
+                                .insert(dir.clone(), archive.join(member));
+                            dirs.push(dir);
+                        }
+                    }
+                    Err(error) => {
+                        tracing::error!(
+                            ?archive,
+                            ?error,
+                            "Failed to extract git dirs from archive."
+                        );
+                    }
+                }
+            }
+            Some(dirs)
+        } else if self.resolve {
+            let mut dirs = Vec::new();
+            for path in &search_paths {
+                match git::resolve_git_dir(&run_opts, path).await {
+                    Ok(dir) => dirs.push(dir),
+                    Err(error) => {
+                        tracing::error!(
+                            ?path,
+                            ?error,
+                            "Failed to resolve git dir."
+                        );
+                    }
+                }
+            }
+            Some(dirs)
+        } else if self.repos_are_roots {
+            let mut dirs = Vec::new();
+            for path in &search_paths {
+                match git::resolve_git_dir(&run_opts, path).await {
+                    // Not a repo containing the path from further up.
+                    Ok(dir) if dir == *path || path.join(".git").exists() => {
+                        dirs.push(dir);
+                    }
+                    _ => {
+                        tracing::debug!(?path, "Not a repo. Walking it.");
+                        dirs.extend(crate::fs::find_dirs(
+                            path,
+                            &self.git_dir_names,
+                            ignore_case,
+                            self.follow,
+                            self.exclude_git_dir_children,
+                            &ignore_paths,
+                            self.print_ignored,
+                        ));
+                    }
+                }
+            }
+            Some(dirs)
+        } else if self.ssh.is_some() {
+            Some(
+                crate::fs::find_dirs_over_ssh(
+                    &target,
+                    &search_paths,
+                    &self.git_dir_names,
+                    ignore_case,
+                    self.follow,
+                    &ignore_paths,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+        if self.count_only {
+            return self
+                .count(&run_opts, resolved, &search_paths, &ignore_paths)
+                .await;
         }
+        let mut checkpoint = match &self.checkpoint_file {
+            Some(file) => Some(Checkpoint::load(file).await?),
+            None => None,
+        };
+        let checkpointed = checkpoint
+            .as_ref()
+            .map(|checkpoint| checkpoint.done.clone());
         let locals: Arc<DashSet<data::Link>> = Arc::new(DashSet::new());
+        let locals_too_large = Arc::new(AtomicUsize::new(0));
+        let exec_ok = Arc::new(AtomicUsize::new(0));
+        let exec_err = Arc::new(AtomicUsize::new(0));
         let remotes_ok: Arc<DashSet<data::Link>> = Arc::new(DashSet::new());
         let remotes_err: Arc<DashSet<data::Link>> = Arc::new(DashSet::new());
+        let manifest_repos: Arc<DashMap<String, manifest::Repo>> =
+            Arc::new(DashMap::new());
 
-        let host = os::hostname().await?;
+        let host = self
+            .host_style
+            .hostname(self.host_name.as_deref(), &target)
+            .await?;
+        // XXX Must outlive the workers, since it deletes the script on drop.
+        let askpass = match &self.token_file {
+            None => None,
+            Some(token_file) => {
+                let token_file = token_file.canonicalize().context(
+                    format!("Invalid token file path={token_file:?}"),
+                )?;
+                Some(git::askpass_helper(&token_file, &self.token_user)?)
+            }
+        };
+        let remote_cache = match &self.remote_cache {
+            None => None,
+            Some(dir) => {
+                tokio::fs::create_dir_all(dir).await?;
+                Some(dir.canonicalize()?)
+            }
+        };
+        if let Some(dir) = &self.clone_tempdir {
+            // Fail now, rather than on every remote later.
+            tempfile::tempfile_in(dir)
+                .context(format!("Unwritable clone tempdir={dir:?}"))?;
+        }
+        let remote_opts = git::RemoteOpts {
+            allow_auth: self.allow_auth,
+            credential_helper: self.credential_helper.clone(),
+            askpass: askpass.as_ref().map(|path| path.to_path_buf()),
+            cache: remote_cache,
+            tempdir: self.clone_tempdir.clone(),
+            depth: self.remote_depth,
+            http_proxy: self.http_proxy.clone(),
+            all_proxy: self.all_proxy.clone(),
+            ssh_command: self.remote_ssh_command.clone(),
+            ssh_connect_timeout: Some(self.ssh_connect_timeout),
+            usage: Arc::default(),
+        };
+        let clone_usage = remote_opts.usage.clone();
+        let download_budget_spent = Arc::new(AtomicBool::new(false));
 
         let (urls_tx, urls_rx) = mpsc::unbounded_channel();
         let (views_tx, views_rx) = mpsc::unbounded_channel();
-        let storage = data::Storage::connect(&self.db_file).await?;
+        let db_file = match (&self.db_file, &self.db_dir) {
+            (Some(db_file), _) => db_file.clone(),
+            (None, Some(db_dir)) => db_dir.join(format!("{host}.db")),
+            (None, None) => PathBuf::from("git-tracker.db"),
+        };
+        let storage = if self.force {
+            data::Storage::connect_force(&db_file).await?
+        } else {
+            data::Storage::connect(&db_file).await?
+        };
         let storage = Arc::new(storage);
+        let known = if self.skip_known {
+            let known = storage.local_dirs(&host).await?;
+            tracing::info!(n = known.len(), "Skipping known repos.");
+            known
+        } else {
+            HashSet::new()
+        };
 
         let locals_worker = tokio::spawn(
             {
                 let host = host.clone();
+                let run_opts = run_opts.clone();
+                let target = target.clone();
+                let remote_opts = remote_opts.clone();
                 let follow = self.follow;
+                let prune = self.exclude_git_dir_children;
+                let print_ignored = self.print_ignored;
+                // Probing would clone here, not on the SSH host.
+                let no_remotes = self.no_remotes || self.ssh.is_some();
+                let identity_only = self.identity_only;
+                let require_remote = self.require_remote.clone();
+                let only_with_remotes = self.only_with_remotes;
+                let only_with_branches = self.only_with_branches;
+                let repo_kind = self.repo_kind;
+                let relative_to = relative_to.clone();
+                let archived = archived.clone();
+                let truncated = truncated.clone();
+                let max_total_repos = self.max_total_repos;
+                let submodule_git_dirs = self.submodule_git_dirs;
+                let cap = max_total_repos.map(Cap::new);
+                let git_dir_names = self.git_dir_names.clone();
+                let resolve = self.resolve;
+                let max_repo_size = self.max_repo_size;
+                let locals_too_large = locals_too_large.clone();
+                let sample = self.sample.map(|n| {
+                    let seed = self
+                        .sample_seed
+                        .unwrap_or_else(|| os::timestamp().unsigned_abs());
+                    tracing::info!(n, seed, "Sampling found dirs.");
+                    (n, seed)
+                });
+                let store_empty_repos = self.store_empty_repos;
+                let path_sep = match (self.print_paths, self.print0) {
+                    (false, _) => None,
+                    (true, false) => Some(b'\n'),
+                    (true, true) => Some(b'\0'),
+                };
+                let exec = self.exec.clone();
+                let exec_ok = exec_ok.clone();
+                let exec_err = exec_err.clone();
                 let locals = locals.clone();
+                let manifest_repos = self
+                    .manifest_file
+                    .is_some()
+                    .then(|| manifest_repos.clone());
                 let views_tx = views_tx.clone();
                 async move {
-                    let git_dirs = search_paths.iter().flat_map(|path| {
-                        crate::fs::find_dirs(
-                            path,
-                            ".git",
-                            follow,
-                            &ignore_paths,
-                        )
-                    });
                     let unique: DashSet<String> = DashSet::new();
-                    // XXX This has been the fastest combination: sync producer + async consumer.
-                    stream::iter(git_dirs)
-                        .for_each_concurrent(None, |dir| async {
-                            if git::is_repo(&dir).await {
+                    let process = |dir: PathBuf| async {
+                        let slot = match &cap {
+                            Some(cap) => match cap.reserve().await {
+                                Some(slot) => Some(slot),
+                                // Hit while waiting for a slot.
+                                None => return,
+                            },
+                            None => None,
+                        };
+                        // As stored, which may be relative.
+                        let is_known = known.contains(&dir)
+                            || relative_to.as_ref().is_some_and(|base| {
+                                dir.strip_prefix(base)
+                                    .is_ok_and(|rel| known.contains(rel))
+                            });
+                        if is_known {
+                            tracing::debug!(?dir, "Skipping known repo.");
+                            return;
+                        }
+                        if is_git_dir(&run_opts, &dir, !resolve, ignore_case)
+                            .await
+                        {
+                            if !repo_kind.matches(&run_opts, &dir).await {
+                                tracing::debug!(
+                                    ?dir,
+                                    ?repo_kind,
+                                    "Skipping repo of another kind."
+                                );
+                                return;
+                            }
+                            let too_large = match max_repo_size {
+                                Some(max) if !identity_only => {
+                                    git::repo_size(&run_opts, &dir)
+                                        .await
+                                        .is_ok_and(|size| size > max)
+                                }
+                                _ => false,
+                            };
+                            let mut view = if too_large {
+                                locals_too_large
+                                    .fetch_add(1, Ordering::Relaxed);
+                                let mut view = git::view_identity(
+                                    &run_opts, &host, &dir,
+                                )
+                                .await;
+                                if let Some(repo) = &mut view.repo {
+                                    repo.skipped_too_large = true;
+                                }
+                                view
+                            } else if identity_only {
+                                git::view_identity(&run_opts, &host, &dir)
+                                    .await
+                            } else {
                                 let link = data::Link::Fs { dir };
-                                let view = git::view(&host, &link).await;
-                                locals.insert(link);
-                                for url in view.repo.iter().flat_map(|repo| {
+                                git::view(
+                                    &run_opts,
+                                    &host,
+                                    &link,
+                                    &remote_opts,
+                                )
+                                .await
+                            };
+                            if let data::Link::Fs { dir } = &mut view.link {
+                                if let Some(archive_dir) = archived.get(dir) {
+                                    dir.clone_from(archive_dir);
+                                }
+                            }
+                            if !has_required_remote(&view, &require_remote) {
+                                tracing::debug!(
+                                    link = ?view.link,
+                                    "Skipping repo without a required \
+                                    remote."
+                                );
+                                return;
+                            }
+                            let (has_remotes, has_branches) = view
+                                .repo
+                                .as_ref()
+                                .map_or((false, false), |repo| {
+                                    (
+                                        !repo.remotes.is_empty(),
+                                        !repo.branches.is_empty(),
+                                    )
+                                });
+                            if (only_with_remotes && !has_remotes)
+                                || (only_with_branches && !has_branches)
+                            {
+                                tracing::debug!(
+                                    link = ?view.link,
+                                    has_remotes,
+                                    has_branches,
+                                    "Skipping repo without remotes or \
+                                    branches."
+                                );
+                                return;
+                            }
+                            if let (Some(cap), Some(slot)) = (&cap, slot) {
+                                cap.accept(slot);
+                            }
+                            if let (Some(sep), data::Link::Fs { dir }) =
+                                (path_sep, &view.link)
+                            {
+                                if let Err(error) = print_path(dir, sep) {
+                                    tracing::error!(
+                                        ?error,
+                                        "Failed to print path."
+                                    );
+                                }
+                            }
+                            if let (Some(exec), data::Link::Fs { dir }) =
+                                (&exec, &view.link)
+                            {
+                                if run_exec(&target, exec, dir).await {
+                                    exec_ok.fetch_add(1, Ordering::Relaxed);
+                                } else {
+                                    exec_err.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                            locals.insert(view.link.clone());
+                            // Sending no URLs leaves the remotes worker
+                            // idle until urls_tx is dropped with us.
+                            let urls = view
+                                .repo
+                                .iter()
+                                .filter(|_| !no_remotes)
+                                .flat_map(|repo| {
                                     repo.remotes.values().cloned()
-                                }) {
-                                    if unique.insert(url.clone()) {
-                                        urls_tx.send(url).unwrap_or_else(
-                                            |_| {
-                                                unreachable!(
-                                                    "urls_rx dropped while \
+                                });
+                            for url in urls {
+                                if unique.insert(url.clone()) {
+                                    urls_tx.send(url).unwrap_or_else(|_| {
+                                        unreachable!(
+                                            "urls_rx dropped while \
                                                     urls_tx is still in use"
-                                                )
-                                            },
-                                        );
-                                    }
+                                        )
+                                    });
                                 }
-                                views_tx.send(view).unwrap_or_else(|_| {
+                            }
+                            if view.repo.is_none() && !store_empty_repos {
+                                tracing::debug!(
+                                    link = ?view.link,
+                                    "Not storing unreadable local repo."
+                                );
+                                return;
+                            }
+                            if let (Some(base), data::Link::Fs { dir }) =
+                                (&relative_to, &mut view.link)
+                            {
+                                if let Ok(rel) = dir.strip_prefix(base) {
+                                    *dir = rel.to_path_buf();
+                                }
+                            }
+                            if let (Some(repos), Some(repo)) = (
+                                &manifest_repos,
+                                manifest::Repo::from_view(&view),
+                            ) {
+                                repos.insert(repo.path.clone(), repo);
+                            }
+                            views_tx
+                                .send(ToStore::View(Box::new(view)))
+                                .unwrap_or_else(|_| {
                                     unreachable!(
                                         "view_rx dropped while view_tx \
                                         is still in use"
                                     )
                                 });
+                        }
+                    };
+                    let within_bounds = |_: &PathBuf| {
+                        let past =
+                            deadline.is_some_and(|d| Instant::now() >= d);
+                        let capped = cap.as_ref().is_some_and(Cap::is_hit);
+                        if capped {
+                            tracing::info!(
+                                max_total_repos,
+                                "Hit the cap on repos. Looking no further."
+                            );
+                        }
+                        if past || capped {
+                            truncated.store(true, Ordering::Relaxed);
+                        }
+                        !past && !capped
+                    };
+                    let with_submodules = |dir: PathBuf| {
+                        let submodules = if submodule_git_dirs {
+                            crate::fs::submodule_git_dirs(&dir)
+                        } else {
+                            Vec::new()
+                        };
+                        std::iter::once(dir).chain(submodules)
+                    };
+                    match (resolved, checkpointed) {
+                        (Some(dirs), _) => {
+                            stream::iter(
+                                dirs.into_iter()
+                                    .flat_map(&with_submodules)
+                                    .take_while(&within_bounds),
+                            )
+                            .for_each_concurrent(None, &process)
+                            .await;
+                        }
+                        (None, None) => {
+                            let git_dirs =
+                                search_paths.iter().flat_map(|path| {
+                                    crate::fs::find_dirs(
+                                        path,
+                                        &git_dir_names,
+                                        ignore_case,
+                                        follow,
+                                        prune,
+                                        &ignore_paths,
+                                        print_ignored,
+                                    )
+                                    .until(deadline, truncated.clone())
+                                });
+                            let git_dirs = git_dirs
+                                .flat_map(&with_submodules)
+                                .take_while(&within_bounds);
+                            match sample {
+                                None => {
+                                    // XXX This has been the fastest combination: sync producer + async consumer.
+                                    stream::iter(git_dirs)
+                                        .for_each_concurrent(None, &process)
+                                        .await;
+                                }
+                                Some((n, seed)) => {
+                                    let dirs = sample_of(git_dirs, n, seed);
+                                    stream::iter(dirs)
+                                        .for_each_concurrent(None, &process)
+                                        .await;
+                                }
                             }
-                        })
-                        .await;
+                        }
+                        (None, Some(checkpointed)) => {
+                            // Subtrees are scanned one at a time, so each
+                            // can be recorded as done once it is.
+                            let subtrees = search_paths
+                                .iter()
+                                .filter(|path| !ignore_paths.contains(*path))
+                                .flat_map(|path| {
+                                    if path.file_name().is_some_and(|name| {
+                                        crate::fs::name_matches(
+                                            name,
+                                            &git_dir_names,
+                                            ignore_case,
+                                        )
+                                    }) {
+                                        vec![path.clone()]
+                                    } else {
+                                        crate::fs::children(path)
+                                    }
+                                });
+                            for subtree in subtrees {
+                                if checkpointed.contains(&subtree) {
+                                    tracing::debug!(
+                                        ?subtree,
+                                        "Skipping checkpointed subtree."
+                                    );
+                                    continue;
+                                }
+                                let git_dirs = crate::fs::find_dirs(
+                                    &subtree,
+                                    &git_dir_names,
+                                    ignore_case,
+                                    follow,
+                                    prune,
+                                    &ignore_paths,
+                                    print_ignored,
+                                )
+                                .until(deadline, truncated.clone());
+                                stream::iter(
+                                    git_dirs
+                                        .flat_map(&with_submodules)
+                                        .take_while(&within_bounds),
+                                )
+                                .for_each_concurrent(None, &process)
+                                .await;
+                                if truncated.load(Ordering::Relaxed) {
+                                    break;
+                                }
+                                // Recorded once its views are stored.
+                                views_tx
+                                    .send(ToStore::Done(subtree))
+                                    .unwrap_or_else(|_| {
+                                        unreachable!(
+                                            "view_rx dropped while view_tx \
+                                            is still in use"
+                                        )
+                                    });
+                            }
+                        }
+                    }
                 }
             }
             .instrument(info_span!("locals_worker"))
             .in_current_span(),
         );
 
+        // URL of the remote which failed authentication, if failing fast.
+        let auth_failed: Arc<OnceLock<String>> = Arc::new(OnceLock::new());
+        let auth_failed_notify = Arc::new(Notify::new());
         let remotes_worker = tokio::spawn(
             {
+                let host = host.clone();
+                let fail_fast_on_auth = self.fail_fast_on_auth;
+                let auth_failed = auth_failed.clone();
+                let auth_failed_notify = auth_failed_notify.clone();
+                let max_download_bytes = self.max_download_bytes;
+                let download_budget_spent = download_budget_spent.clone();
                 let views_tx = views_tx.clone();
                 let remotes_ok = remotes_ok.clone();
                 let remotes_err = remotes_err.clone();
+                // Sizes are only known once cloned, so checking the budget
+                // before each probe only holds it if none are in flight.
+                let limit = max_download_bytes.map(|_| 1);
                 async move {
                     UnboundedReceiverStream::new(urls_rx)
-                        .for_each_concurrent(None, {
+                        .for_each_concurrent(limit, {
                             move |url: String| {
                                 let host = host.clone();
+                                let run_opts = run_opts.clone();
+                                let remote_opts = remote_opts.clone();
                                 let remotes_ok = remotes_ok.clone();
                                 let remotes_err = remotes_err.clone();
                                 let views_tx = views_tx.clone();
+                                let auth_failed = auth_failed.clone();
+                                let auth_failed_notify =
+                                    auth_failed_notify.clone();
+                                let download_budget_spent =
+                                    download_budget_spent.clone();
                                 async move {
+                                    if max_download_bytes.is_some_and(|max| {
+                                        remote_opts.usage.downloaded() >= max
+                                    }) {
+                                        if !download_budget_spent
+                                            .swap(true, Ordering::Relaxed)
+                                        {
+                                            tracing::warn!(
+                                                "Download budget spent. \
+                                                Not probing further remotes."
+                                            );
+                                        }
+                                        return;
+                                    }
                                     let link = data::Link::Net { url };
-                                    let view = git::view(&host, &link).await;
+                                    let view =
+                                        git::view(&run_opts, &host, &link, &remote_opts)
+                                            .await;
+                                    if view.is_unread_for_lack_of_resources() {
+                                        tracing::warn!(
+                                            ?link,
+                                            "Not storing remote unread for \
+                                            lack of local resources."
+                                        );
+                                        return;
+                                    }
+                                    let auth_required = view.failure
+                                        == Some(
+                                            data::RemoteFailure::AuthRequired,
+                                        );
+                                    let auth_failed_url = (fail_fast_on_auth
+                                        && auth_required)
+                                        .then(|| link.to_string());
                                     if view.repo.is_some() {
                                         remotes_ok.insert(link);
                                     } else {
                                         remotes_err.insert(link);
                                     }
-                                    views_tx.send(view).unwrap_or_else(
+                                    views_tx.send(ToStore::View(Box::new(view))).unwrap_or_else(
                                         |_| {
                                             unreachable!(
                                                 "view_rx dropped while view_tx \
@@ -135,6 +1247,10 @@ impl Cmd {
                                             )
                                         },
                                     );
+                                    if let Some(url) = auth_failed_url {
+                                        let _ = auth_failed.set(url);
+                                        auth_failed_notify.notify_one();
+                                    }
                                 }
                             }
                         })
@@ -146,16 +1262,40 @@ impl Cmd {
         );
 
         let batch_size = self.batch_size;
+        let stored = Arc::new(AtomicUsize::new(0));
+        // Flushed line by line, so no view is left half-written.
+        let mut jsonl_out = match &self.jsonl_out {
+            None => None,
+            Some(file) => Some(io::LineWriter::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(file)
+                    .context(format!("Invalid JSONL file path={file:?}"))?,
+            )),
+        };
         let storage_worker = tokio::spawn(
-            async move {
-                UnboundedReceiverStream::new(views_rx)
-                    .chunks(batch_size)
-                    .for_each_concurrent(None, move |views| {
+            {
+                let stored = stored.clone();
+                async move {
+                    let mut views_rx = views_rx;
+                    let mut batch = Vec::with_capacity(batch_size);
+                    let mut stores = tokio::task::JoinSet::new();
+                    // Whether all batches stored since the last subtree was
+                    // recorded as done were stored successfully.
+                    let mut all_stored = true;
+                    let store = |views: Vec<data::View>| {
                         let storage = storage.clone();
+                        let stored = stored.clone();
                         async move {
                             match storage.store_views(&views[..]).await {
                                 Ok(()) => {
+                                    stored.fetch_add(
+                                        views.len(),
+                                        Ordering::Relaxed,
+                                    );
                                     tracing::info!("Views store succeeded.");
+                                    true
                                 }
                                 Err(error) => {
                                     // TODO Exit app on storage failure?
@@ -163,27 +1303,745 @@ impl Cmd {
                                         ?error,
                                         "Views store failed."
                                     );
+                                    false
                                 }
                             }
                         }
-                    })
-                    .await;
+                    };
+                    while let Some(to_store) = views_rx.recv().await {
+                        while let Some(ok) = stores.try_join_next() {
+                            all_stored &= ok.unwrap_or(false);
+                        }
+                        match to_store {
+                            ToStore::View(view) => {
+                                if let Some(out) = &mut jsonl_out {
+                                    if let Err(error) =
+                                        write_jsonl(out, &view)
+                                    {
+                                        tracing::error!(
+                                            ?error,
+                                            "Failed to write view to JSONL."
+                                        );
+                                    }
+                                }
+                                batch.push(*view);
+                                if batch.len() >= batch_size {
+                                    stores.spawn(store(std::mem::take(
+                                        &mut batch,
+                                    )));
+                                }
+                            }
+                            ToStore::Done(subtree) => {
+                                if !batch.is_empty() {
+                                    stores.spawn(store(std::mem::take(
+                                        &mut batch,
+                                    )));
+                                }
+                                while let Some(ok) = stores.join_next().await
+                                {
+                                    all_stored &= ok.unwrap_or(false);
+                                }
+                                let Some(checkpoint) = &mut checkpoint else {
+                                    continue;
+                                };
+                                if !all_stored {
+                                    // Rescanned on resume instead.
+                                    tracing::warn!(
+                                        ?subtree,
+                                        "Not checkpointing subtree with \
+                                        views which failed to store."
+                                    );
+                                } else if let Err(error) =
+                                    checkpoint.complete(&subtree).await
+                                {
+                                    tracing::error!(
+                                        ?subtree,
+                                        ?error,
+                                        "Failed to checkpoint subtree."
+                                    );
+                                }
+                                all_stored = true;
+                            }
+                        }
+                    }
+                    if !batch.is_empty() {
+                        stores.spawn(store(batch));
+                    }
+                    while stores.join_next().await.is_some() {}
+                }
             }
             .instrument(info_span!("storage_worker"))
             .in_current_span(),
         );
 
-        let _ = locals_worker.await;
-        let _ = remotes_worker.await;
+        let locals_abort = locals_worker.abort_handle();
+        let remotes_abort = remotes_worker.abort_handle();
+        #[cfg(unix)]
+        let status_worker = {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut usr1 = signal(SignalKind::user_defined1())?;
+            let locals = locals.clone();
+            let remotes_ok = remotes_ok.clone();
+            let remotes_err = remotes_err.clone();
+            let stored = stored.clone();
+            let locals_abort = locals_abort.clone();
+            let remotes_abort = remotes_abort.clone();
+            tokio::spawn(async move {
+                while usr1.recv().await.is_some() {
+                    let phase = if !locals_abort.is_finished() {
+                        "finding"
+                    } else if !remotes_abort.is_finished() {
+                        "probing"
+                    } else {
+                        "storing"
+                    };
+                    eprintln!(
+                        "status phase={phase} locals={} remotes_ok={} \
+                        remotes_err={} stored={} elapsed_secs={:.3}",
+                        locals.len(),
+                        remotes_ok.len(),
+                        remotes_err.len(),
+                        stored.load(Ordering::Relaxed),
+                        started.elapsed().as_secs_f64(),
+                    );
+                }
+            })
+        };
+        let workers = async {
+            let _ = locals_worker.await;
+            let _ = remotes_worker.await;
+        };
+        let cancel_at = deadline.filter(|_| self.deadline_cancel);
+        let cancel = async {
+            match cancel_at {
+                Some(at) => tokio::time::sleep_until(at.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+        let interrupted = tokio::select! {
+            () = workers => false,
+            signal = tokio::signal::ctrl_c() => {
+                signal?;
+                tracing::warn!("Interrupted. Storing views found so far.");
+                true
+            }
+            () = cancel => {
+                tracing::warn!("Deadline passed. Storing views found so far.");
+                true
+            }
+            () = auth_failed_notify.notified() => {
+                tracing::error!(
+                    "Remote requires authentication. Storing views found \
+                    so far."
+                );
+                true
+            }
+        };
+        if interrupted {
+            // Aborted workers drop their views_tx clones, letting the
+            // storage worker drain what they already sent.
+            locals_abort.abort();
+            remotes_abort.abort();
+        }
         drop(views_tx); // XXX Otherwise view_rx blocks forever.
         let _ = storage_worker.await;
+        #[cfg(unix)]
+        status_worker.abort();
+        if let Some(url) = auth_failed.get() {
+            anyhow::bail!(
+                "Remote requires authentication, so credentials are likely \
+                wrong: url={:?}",
+                redact::credentials(url)
+            );
+        }
+        if interrupted {
+            tracing::warn!(
+                stored = stored.load(Ordering::Relaxed),
+                "Stored views found before the interruption."
+            );
+        } else if let Some(file) = &self.checkpoint_file {
+            match tokio::fs::remove_file(file).await {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                    tracing::error!(
+                        ?file,
+                        ?error,
+                        "Failed to remove checkpoint file."
+                    );
+                }
+                _ => {}
+            }
+        }
 
+        let summary = Summary::new(
+            started.elapsed(),
+            locals.len(),
+            locals_too_large.load(Ordering::Relaxed),
+            remotes_ok.len(),
+            remotes_err.len(),
+            (
+                exec_ok.load(Ordering::Relaxed),
+                exec_err.load(Ordering::Relaxed),
+            ),
+            &clone_usage,
+            interrupted
+                || truncated.load(Ordering::Relaxed)
+                || download_budget_spent.load(Ordering::Relaxed),
+        );
         tracing::info!(
-            locals = locals.len(),
-            remotes_ok = remotes_ok.len(),
-            remotes_err = remotes_err.len(),
+            locals = summary.locals,
+            locals_too_large = summary.locals_too_large,
+            remotes_ok = summary.remotes_ok,
+            remotes_err = summary.remotes_err,
+            remotes_downloaded_bytes = summary.remotes_downloaded_bytes,
+            remotes_temp_peak_bytes = summary.remotes_temp_peak_bytes,
+            exec_ok = summary.exec_ok,
+            exec_err = summary.exec_err,
+            duration_secs = summary.duration_secs,
+            locals_per_sec = summary.locals_per_sec,
+            remotes_per_sec = summary.remotes_per_sec,
+            truncated = summary.truncated,
             "Final counts."
         );
+        match self.output {
+            None => {}
+            Some(super::Output::Human) => print!("{summary}"),
+            Some(super::Output::Json) => {
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+            }
+        }
+        if self.profile {
+            eprint!("{}", Profile(crate::profile::totals()));
+        }
+        if let Some(metrics_file) = &self.metrics_file {
+            crate::os::write_whole(metrics_file, summary.metrics(&host))
+                .await?;
+        }
+        if let Some(manifest_file) = &self.manifest_file {
+            let repos = manifest_repos.iter().map(|e| e.value().clone());
+            manifest::Manifest::new(&host, repos)
+                .write(manifest_file)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The --count-only scan.
+    async fn count(
+        &self,
+        run: &git::RunOpts,
+        resolved: Option<Vec<PathBuf>>,
+        search_paths: &[PathBuf],
+        ignore_paths: &HashSet<PathBuf>,
+    ) -> anyhow::Result<()> {
+        let dirs: Box<dyn Iterator<Item = PathBuf> + Send> = match resolved {
+            Some(dirs) => Box::new(dirs.into_iter()),
+            None => {
+                let git_dir_names = self.git_dir_names.clone();
+                let (follow, prune) =
+                    (self.follow, self.exclude_git_dir_children);
+                let print_ignored = self.print_ignored;
+                let ignore_case = self.name_case.ignore_case();
+                let ignore_paths = ignore_paths.clone();
+                Box::new(search_paths.iter().cloned().flat_map(move |path| {
+                    crate::fs::find_dirs(
+                        &path,
+                        &git_dir_names,
+                        ignore_case,
+                        follow,
+                        prune,
+                        &ignore_paths,
+                        print_ignored,
+                    )
+                }))
+            }
+        };
+        let count = AtomicUsize::new(0);
+        stream::iter(dirs)
+            .for_each_concurrent(None, |dir| {
+                let count = &count;
+                async move {
+                    if is_git_dir(
+                        run,
+                        &dir,
+                        !self.resolve,
+                        self.name_case.ignore_case(),
+                    )
+                    .await
+                    {
+                        count.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+            .await;
+        let count = count.into_inner();
+        match self.output {
+            None | Some(super::Output::Human) => println!("{count}"),
+            Some(super::Output::Json) => {
+                println!("{}", serde_json::json!({ "locals": count }));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Table of the time spent in each span.
+struct Profile(BTreeMap<&'static str, crate::profile::Total>);
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows: Vec<_> = self.0.iter().collect();
+        rows.sort_by_key(|(_, t)| std::cmp::Reverse(t.time));
+        writeln!(
+            f,
+            "{:<24} {:>10} {:>12} {:>10}",
+            "span", "count", "total_secs", "mean_ms"
+        )?;
+        for (name, total) in rows {
+            let mean_ms = if total.count == 0 {
+                0.0
+            } else {
+                total.time.as_secs_f64() * 1000.0 / total.count as f64
+            };
+            writeln!(
+                f,
+                "{:<24} {:>10} {:>12.3} {:>10.3}",
+                name,
+                total.count,
+                total.time.as_secs_f64(),
+                mean_ms
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Uniformly random `n` of the items, by reservoir sampling, so only `n`
+/// are held at a time.
+fn sample_of<T>(
+    items: impl Iterator<Item = T>,
+    n: usize,
+    seed: u64,
+) -> Vec<T> {
+    let mut rng = SplitMix64(seed);
+    let mut sample = Vec::with_capacity(n);
+    for (i, item) in items.enumerate() {
+        if i < n {
+            sample.push(item);
+        } else {
+            let j = rng.next_u64() % (i as u64 + 1);
+            if let Some(slot) =
+                usize::try_from(j).ok().and_then(|j| sample.get_mut(j))
+            {
+                *slot = item;
+            }
+        }
+    }
+    sample
+}
+
+/// Small seedable PRNG, which is plenty for picking samples.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// What the storage worker is sent.
+enum ToStore {
+    View(Box<data::View>),
+    /// A subtree of the search paths was scanned, so is to be recorded in
+    /// the checkpoint once all views sent before are stored.
+    Done(PathBuf),
+}
+
+/// Subtrees of the search paths which were fully scanned, one path per
+/// line of the file.
+struct Checkpoint {
+    file: PathBuf,
+    done: HashSet<PathBuf>,
+}
+
+impl Checkpoint {
+    async fn load(file: &Path) -> anyhow::Result<Self> {
+        let done = match tokio::fs::read_to_string(file).await {
+            Ok(text) => text.lines().map(PathBuf::from).collect(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                HashSet::new()
+            }
+            Err(error) => {
+                return Err(error).context(format!(
+                    "Failed to read checkpoint file={file:?}"
+                ));
+            }
+        };
+        Ok(Self {
+            file: file.to_path_buf(),
+            done,
+        })
+    }
+
+    /// Appends the subtree to the file right away, since the scan may be
+    /// interrupted at any time. Called once its views are stored, but
+    /// views of remotes found in it may still be on their way.
+    async fn complete(&mut self, subtree: &Path) -> anyhow::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        match subtree.to_str() {
+            Some(line) if !line.contains('\n') => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.file)
+                    .await?;
+                file.write_all(format!("{line}\n").as_bytes()).await?;
+                file.flush().await?;
+            }
+            _ => {
+                // Could not be read back intact, so it's rescanned instead.
+                tracing::warn!(?subtree, "Not checkpointing odd path.");
+            }
+        }
+        self.done.insert(subtree.to_path_buf());
         Ok(())
     }
 }
+
+/// Slots for the repos `--max-total-repos` lets in. One is held from when
+/// a dir is dispatched, so no more are read at once than could still be
+/// let in. Skipping the dir frees its slot for another, and accepting it
+/// uses the slot up, until none are left.
+#[derive(Debug)]
+struct Cap {
+    max: usize,
+    slots: Semaphore,
+    accepted: AtomicUsize,
+}
+
+impl Cap {
+    fn new(max: usize) -> Self {
+        let slots = Semaphore::new(max.min(Semaphore::MAX_PERMITS));
+        if max == 0 {
+            slots.close();
+        }
+        Self {
+            max,
+            slots,
+            accepted: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits for a slot, if the cap isn't hit before one frees up.
+    async fn reserve(&self) -> Option<SemaphorePermit<'_>> {
+        self.slots.acquire().await.ok()
+    }
+
+    fn accept(&self, slot: SemaphorePermit<'_>) {
+        slot.forget();
+        if self.accepted.fetch_add(1, Ordering::Relaxed) + 1 >= self.max {
+            // Wakes those still waiting, with nothing.
+            self.slots.close();
+        }
+    }
+
+    fn is_hit(&self) -> bool {
+        self.accepted.load(Ordering::Relaxed) >= self.max
+    }
+}
+
+/// Whether the dir found is a git dir. Unless `check_bare` is off, those
+/// not named ".git" are only taken for bare repos, or submodules under
+/// `.git/modules`, since the name patterns can be loose. ".git" is in any
+/// case if case is ignored.
+async fn is_git_dir(
+    run: &git::RunOpts,
+    dir: &Path,
+    check_bare: bool,
+    ignore_case: bool,
+) -> bool {
+    git::is_repo(run, dir).await
+        && (!check_bare
+            || crate::fs::is_dot_git(dir, ignore_case)
+            || dir.ancestors().any(|dir| dir.ends_with(".git/modules"))
+            || git::is_bare(run, dir).await.is_ok_and(|is_bare| is_bare))
+}
+
+/// Whether any remote URL of the repo contains any of the patterns, or
+/// there are no patterns to require.
+fn has_required_remote(view: &data::View, patterns: &[String]) -> bool {
+    patterns.is_empty()
+        || view
+            .repo
+            .iter()
+            .flat_map(|repo| repo.remotes.values())
+            .any(|url| patterns.iter().any(|p| url.contains(p.as_str())))
+}
+
+fn write_jsonl(
+    out: &mut impl io::Write,
+    view: &data::View,
+) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, view)?;
+    out.write_all(b"\n")
+}
+
+/// Runs the `--exec` command on the repo, printing its output whole, so
+/// concurrent runs don't interleave. Whether it succeeded.
+async fn run_exec(target: &os::Target, exec: &str, dir: &Path) -> bool {
+    let command =
+        exec.replace("{}", &os::shell_quote(&dir.to_string_lossy()));
+    match target.cmd("sh", &["-c", command.as_str()]).await {
+        Ok(stdout) => {
+            let mut out = io::stdout().lock();
+            if let Err(error) =
+                out.write_all(&stdout).and_then(|()| out.flush())
+            {
+                tracing::error!(?error, "Failed to print exec output.");
+            }
+            true
+        }
+        Err(error) => {
+            tracing::error!(?dir, ?command, ?error, "Exec failed.");
+            false
+        }
+    }
+}
+
+/// Writes the path's bytes as they are, so even non-UTF-8 paths survive.
+fn print_path(path: &Path, sep: u8) -> io::Result<()> {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+
+        path.as_os_str().as_bytes()
+    };
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy();
+    #[cfg(not(unix))]
+    let bytes = bytes.as_bytes();
+
+    let mut line = Vec::with_capacity(bytes.len() + 1);
+    line.extend_from_slice(bytes);
+    line.push(sep);
+    // Whole line in one write, so lines from concurrent tasks don't mix.
+    io::stdout().lock().write_all(&line)
+}
+
+#[derive(Serialize, Debug)]
+struct Summary {
+    locals: usize,
+    /// Local repos of which only the roots were read, for their size.
+    locals_too_large: usize,
+    remotes_ok: usize,
+    remotes_err: usize,
+    /// Bytes taken by remote clones, as a measure of what was downloaded.
+    remotes_downloaded_bytes: u64,
+    /// Most bytes taken at once by temporary remote clones.
+    remotes_temp_peak_bytes: u64,
+    /// Runs of `--exec` which exited successfully.
+    exec_ok: usize,
+    /// Runs of `--exec` which failed or couldn't be started.
+    exec_err: usize,
+    duration_secs: f64,
+    locals_per_sec: f64,
+    remotes_per_sec: f64,
+    /// Whether the scan stopped early, at the deadline or the repo cap, on
+    /// Ctrl-C, or with the download budget spent.
+    truncated: bool,
+}
+
+impl Summary {
+    #[allow(clippy::cast_precision_loss, clippy::too_many_arguments)]
+    fn new(
+        duration: Duration,
+        locals: usize,
+        locals_too_large: usize,
+        remotes_ok: usize,
+        remotes_err: usize,
+        (exec_ok, exec_err): (usize, usize),
+        clone_usage: &git::CloneUsage,
+        truncated: bool,
+    ) -> Self {
+        let duration_secs = duration.as_secs_f64();
+        let per_sec = |n: usize| {
+            if duration_secs > 0.0 {
+                n as f64 / duration_secs
+            } else {
+                0.0
+            }
+        };
+        Self {
+            locals,
+            locals_too_large,
+            remotes_ok,
+            remotes_err,
+            remotes_downloaded_bytes: clone_usage.downloaded(),
+            remotes_temp_peak_bytes: clone_usage.temp_peak(),
+            exec_ok,
+            exec_err,
+            duration_secs,
+            locals_per_sec: per_sec(locals),
+            remotes_per_sec: per_sec(remotes_ok + remotes_err),
+            truncated,
+        }
+    }
+
+    /// Prometheus text exposition format.
+    #[allow(clippy::cast_precision_loss)]
+    fn metrics(&self, host: &str) -> String {
+        let host = host
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        let gauges = [
+            ("locals", "Local repos found.", self.locals as f64),
+            (
+                "locals_too_large",
+                "Local repos only partly read for their size.",
+                self.locals_too_large as f64,
+            ),
+            ("remotes_ok", "Remote repos read.", self.remotes_ok as f64),
+            (
+                "remotes_err",
+                "Remote repos failed to read.",
+                self.remotes_err as f64,
+            ),
+            (
+                "remotes_downloaded_bytes",
+                "Bytes taken by remote clones.",
+                self.remotes_downloaded_bytes as f64,
+            ),
+            (
+                "remotes_temp_peak_bytes",
+                "Most bytes taken at once by temporary remote clones.",
+                self.remotes_temp_peak_bytes as f64,
+            ),
+            ("exec_ok", "Exec runs succeeded.", self.exec_ok as f64),
+            ("exec_err", "Exec runs failed.", self.exec_err as f64),
+            (
+                "duration_seconds",
+                "Duration of the scan.",
+                self.duration_secs,
+            ),
+            (
+                "repos_per_second",
+                "Local and remote repos read per second.",
+                self.locals_per_sec + self.remotes_per_sec,
+            ),
+            (
+                "truncated",
+                "Whether the scan stopped early.",
+                f64::from(u8::from(self.truncated)),
+            ),
+        ];
+        let mut out = String::new();
+        for (name, help, value) in gauges {
+            let _ = writeln!(out, "# HELP git_tracker_{name} {help}");
+            let _ = writeln!(out, "# TYPE git_tracker_{name} gauge");
+            let _ = writeln!(
+                out,
+                "git_tracker_{name}{{host=\"{host}\"}} {value}"
+            );
+        }
+        out
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "locals          {}", self.locals)?;
+        if self.locals_too_large > 0 {
+            writeln!(
+                f,
+                "  too_large     {}",
+                Paint::Yellow.paint(self.locals_too_large)
+            )?;
+        }
+        writeln!(
+            f,
+            "remotes_ok      {}",
+            Paint::Green.paint(self.remotes_ok)
+        )?;
+        writeln!(
+            f,
+            "remotes_err     {}",
+            Paint::Red.paint(self.remotes_err)
+        )?;
+        if self.remotes_downloaded_bytes > 0 {
+            writeln!(f, "  downloaded    {}", self.remotes_downloaded_bytes)?;
+            writeln!(f, "  temp_peak     {}", self.remotes_temp_peak_bytes)?;
+        }
+        if self.exec_ok + self.exec_err > 0 {
+            writeln!(
+                f,
+                "exec_ok         {}",
+                Paint::Green.paint(self.exec_ok)
+            )?;
+            writeln!(
+                f,
+                "exec_err        {}",
+                Paint::Red.paint(self.exec_err)
+            )?;
+        }
+        writeln!(f, "duration_secs   {:.3}", self.duration_secs)?;
+        writeln!(f, "locals_per_sec  {:.3}", self.locals_per_sec)?;
+        writeln!(f, "remotes_per_sec {:.3}", self.remotes_per_sec)?;
+        if self.truncated {
+            writeln!(f, "{}", Paint::Yellow.paint("truncated"))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_as_flags() {
+        use clap::{Args, FromArgMatches};
+
+        let matches = Cmd::augment_args(clap::Command::new("find"))
+            .try_get_matches_from(["find"])
+            .unwrap();
+        let cmd = Cmd::from_arg_matches(&matches).unwrap();
+        assert_eq!(FindOptions::from(&cmd), FindOptions::default());
+    }
+
+    #[tokio::test]
+    async fn no_search_paths_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_file = dir.path().join("db");
+        let opts = FindOptions {
+            db_file: Some(db_file.clone()),
+            ..FindOptions::default()
+        };
+        let error = opts.run().await.unwrap_err();
+        assert!(error.to_string().contains("No search paths given"));
+        assert!(!db_file.exists());
+    }
+
+    #[tokio::test]
+    async fn cap_holds_slots_until_skipped_or_accepted() {
+        let cap = Cap::new(2);
+        let first = cap.reserve().await.unwrap();
+        let second = cap.reserve().await.unwrap();
+        // Both in flight, so a third dir isn't read yet.
+        assert!(cap.slots.try_acquire().is_err());
+        // Skipped.
+        drop(first);
+        let third = cap.reserve().await.unwrap();
+        cap.accept(second);
+        assert!(!cap.is_hit());
+        let (waited, ()) =
+            tokio::join!(cap.reserve(), async { cap.accept(third) });
+        assert!(waited.is_none());
+        assert!(cap.is_hit());
+    }
+}
@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+
+use super::Paint;
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Host which scanned the repo. Defaults to this host.
+    #[clap(long)]
+    host: Option<String>,
+
+    /// How to spell this host's name, when defaulting to it, to match how
+    /// it was scanned.
+    #[clap(long, value_enum, default_value_t = super::HostStyle::Raw)]
+    host_style: super::HostStyle,
+
+    /// Local git dir or remote URL, as it was scanned.
+    link: data::Link,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let host = self
+            .host_style
+            .hostname(self.host.as_deref(), &crate::os::Target::Local)
+            .await?;
+        let link = match &self.link {
+            data::Link::Fs { dir } => data::Link::Fs {
+                dir: dir.canonicalize().unwrap_or_else(|_| dir.clone()),
+            },
+            link @ data::Link::Net { .. } => link.clone(),
+        };
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let views = storage.history(&host, &link).await?;
+        if views.is_empty() {
+            tracing::warn!(?host, ?link, "No views found.");
+        }
+        let mut prev: Option<&data::Repo> = None;
+        for view in &views {
+            println!("scanned_at={}", view.scanned_at);
+            match (prev, &view.repo) {
+                (_, None) => {
+                    println!("  {}", Paint::Red.paint("unreadable"));
+                    if let Some(error) = &view.error {
+                        println!("  error={error:?}");
+                    }
+                }
+                (None, Some(repo)) => println!(
+                    "  branches={} remotes={}",
+                    repo.branches.len(),
+                    repo.remotes.len()
+                ),
+                (Some(old), Some(new)) => {
+                    let diff = old.diff(new);
+                    if diff.is_empty() {
+                        println!("  unchanged");
+                    }
+                    for line in diff.to_string().lines() {
+                        println!("  {}", Paint::diff_line(line));
+                    }
+                }
+            }
+            if view.repo.is_some() {
+                prev = view.repo.as_ref();
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+}
+
+#[derive(Serialize, Debug)]
+struct Host {
+    host: String,
+    /// Number of links, local and remote, with views from this host.
+    repos: i64,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let hosts: Vec<Host> = storage
+            .hosts()
+            .await?
+            .into_iter()
+            .map(|(host, repos)| Host { host, repos })
+            .collect();
+        match self.output {
+            super::Output::Human => {
+                for Host { host, repos } in &hosts {
+                    println!("{repos:>8} {host}");
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&hosts)?);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,354 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use tokio::io::{self, AsyncBufRead, AsyncBufReadExt};
+
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Database insertion batch size - how may views to pack into a single transaction.
+    #[clap(short, long, default_value_t = 1000)]
+    batch_size: usize,
+
+    /// Use the database file even if it doesn't look like one of ours,
+    /// adding our tables to it.
+    #[clap(long, default_value_t = false)]
+    force: bool,
+
+    /// Record in this file which hosts have all their views stored, and
+    /// skip those hosts when importing again, so an interrupted import can
+    /// be resumed, storing again in the history only views of the host it
+    /// was interrupted in. A host is done once views of another follow, so
+    /// each host's views must be together in the input, as `export` writes
+    /// them. The file is removed once an import completes.
+    #[clap(long)]
+    checkpoint_file: Option<PathBuf>,
+
+    /// File written by `export`, either NDJSON or a JSON array with one
+    /// view per line. Reads stdin when omitted or "-".
+    input: Option<PathBuf>,
+}
+
+impl Cmd {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = if self.force {
+            data::Storage::connect_force(&self.db_file).await?
+        } else {
+            data::Storage::connect(&self.db_file).await?
+        };
+        let input: Box<dyn AsyncBufRead + Unpin + Send> = match &self.input {
+            Some(path) if path.as_os_str() != "-" => {
+                let file = tokio::fs::File::open(path)
+                    .await
+                    .context(format!("Failed to open input file={path:?}"))?;
+                Box::new(io::BufReader::new(file))
+            }
+            _ => Box::new(io::BufReader::new(io::stdin())),
+        };
+        let counts = self.import(&storage, input).await?;
+        tracing::info!(
+            imported = counts.imported,
+            skipped = counts.skipped,
+            malformed = counts.malformed,
+            "Import done."
+        );
+        Ok(())
+    }
+
+    async fn import<R: AsyncBufRead + Unpin>(
+        &self,
+        storage: &data::Storage,
+        input: R,
+    ) -> anyhow::Result<Counts> {
+        let mut checkpoint = match &self.checkpoint_file {
+            Some(file) => Some(Checkpoint::load(file).await?),
+            None => None,
+        };
+        // Host whose views are being read, and those before it, whose
+        // views may still be in the batch, to record as done once stored.
+        let mut current: Option<String> = None;
+        let mut finished: Vec<String> = Vec::new();
+        // Hosts read in full in this run, whose views should not recur.
+        let mut ended: HashSet<String> = HashSet::new();
+        let mut skipped: usize = 0;
+        let mut lines = input.lines();
+        let mut batch = Vec::with_capacity(self.batch_size);
+        let mut line_num: usize = 0;
+        let mut imported: usize = 0;
+        let mut malformed: usize = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_num += 1;
+            let line = line.trim();
+            let line = line.strip_prefix(',').unwrap_or(line);
+            let line = line.strip_suffix(',').unwrap_or(line);
+            if matches!(line, "" | "[" | "]") {
+                continue;
+            }
+            match serde_json::from_str::<data::View>(line) {
+                Ok(view) => {
+                    if checkpoint.is_some() && ended.contains(&view.host) {
+                        bail!(
+                            "Views of host={:?} are not all together in the \
+                            input, at line {line_num}, so it can't be \
+                            checkpointed",
+                            view.host
+                        );
+                    }
+                    if checkpoint.as_ref().is_some_and(|checkpoint| {
+                        checkpoint.is_done(&view.host)
+                    }) {
+                        skipped += 1;
+                        continue;
+                    }
+                    if current.as_ref() != Some(&view.host) {
+                        if let Some(host) = current.replace(view.host.clone())
+                        {
+                            ended.insert(host.clone());
+                            finished.push(host);
+                        }
+                    }
+                    batch.push(view);
+                }
+                Err(error) => {
+                    malformed += 1;
+                    tracing::warn!(line_num, ?error, "Malformed view.");
+                }
+            }
+            if batch.len() >= self.batch_size {
+                storage.store_views(&batch[..]).await?;
+                if let Some(checkpoint) = &mut checkpoint {
+                    checkpoint.complete(&finished).await?;
+                }
+                finished.clear();
+                imported += batch.len();
+                batch.clear();
+            }
+        }
+        storage.store_views(&batch[..]).await?;
+        imported += batch.len();
+        if let Some(checkpoint) = &mut checkpoint {
+            // Only now is the last host known to be done.
+            finished.extend(current);
+            checkpoint.complete(&finished).await?;
+        }
+        if let Some(file) = &self.checkpoint_file {
+            match tokio::fs::remove_file(file).await {
+                Err(error)
+                    if error.kind() != std::io::ErrorKind::NotFound =>
+                {
+                    tracing::error!(
+                        ?file,
+                        ?error,
+                        "Failed to remove checkpoint file."
+                    );
+                }
+                _ => {}
+            }
+        }
+        Ok(Counts {
+            imported,
+            skipped,
+            malformed,
+        })
+    }
+}
+
+/// Views read from the input, by what became of them.
+#[derive(Debug, PartialEq, Eq)]
+struct Counts {
+    imported: usize,
+    /// Already stored, as recorded in the checkpoint.
+    skipped: usize,
+    malformed: usize,
+}
+
+/// Hosts all of whose views are stored, as a JSON array.
+struct Checkpoint {
+    file: PathBuf,
+    done: BTreeSet<String>,
+}
+
+impl Checkpoint {
+    async fn load(file: &Path) -> anyhow::Result<Self> {
+        let done = match tokio::fs::read(file).await {
+            Ok(json) => serde_json::from_slice(&json)
+                .context(format!("Malformed checkpoint file={file:?}"))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                BTreeSet::new()
+            }
+            Err(error) => {
+                return Err(error).context(format!(
+                    "Failed to read checkpoint file={file:?}"
+                ));
+            }
+        };
+        Ok(Self {
+            file: file.to_path_buf(),
+            done,
+        })
+    }
+
+    fn is_done(&self, host: &str) -> bool {
+        self.done.contains(host)
+    }
+
+    /// Records the hosts, once all their views are stored, and rewrites
+    /// the file right away, since the import may be interrupted at any
+    /// time.
+    async fn complete(&mut self, hosts: &[String]) -> anyhow::Result<()> {
+        if hosts.is_empty() {
+            return Ok(());
+        }
+        self.done.extend(hosts.iter().cloned());
+        crate::os::write_whole(&self.file, serde_json::to_vec(&self.done)?)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{StreamExt, TryStreamExt};
+
+    use super::*;
+    use crate::{cmd::export, data::tests::view};
+
+    fn cmd(db_file: &Path) -> Cmd {
+        Cmd {
+            db_file: db_file.to_path_buf(),
+            batch_size: 2,
+            force: false,
+            checkpoint_file: None,
+            input: None,
+        }
+    }
+
+    async fn sorted(
+        views: futures::stream::BoxStream<'_, anyhow::Result<data::View>>,
+    ) -> Vec<data::View> {
+        let mut views: Vec<data::View> = views.try_collect().await.unwrap();
+        views.sort_by_key(|view| {
+            (view.host.clone(), view.link.clone(), view.scanned_at)
+        });
+        views
+    }
+
+    #[tokio::test]
+    async fn export_then_import_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = data::Storage::connect(dir.path().join("source.db"))
+            .await
+            .unwrap();
+        let mut rescanned = view("a", "/src/one", &[("up", "https://x.org")]);
+        rescanned.scanned_at = 2;
+        let mut unreadable = view("b", "https://y.org/gone.git", &[]);
+        unreadable.repo = None;
+        unreadable.reachable = Some(false);
+        unreadable.failure = Some(data::RemoteFailure::NotFound);
+        unreadable.error = Some("Repository not found.".to_string());
+        source
+            .store_views(&[
+                view("a", "/src/one", &[]),
+                view("a", "/src/two", &[]),
+                unreadable,
+            ])
+            .await
+            .unwrap();
+        source.store_views(&[rescanned]).await.unwrap();
+
+        for format in [export::Format::Ndjson, export::Format::Json] {
+            for history in [false, true] {
+                let views = || {
+                    if history {
+                        source.history_stream()
+                    } else {
+                        source.views_stream()
+                    }
+                };
+                let mut out = Vec::new();
+                export::write_views(format, views(), &mut out)
+                    .await
+                    .unwrap();
+                let mut input = String::from_utf8(out).unwrap();
+                input.insert_str(input.find('\n').unwrap() + 1, "{oops\n");
+
+                let file = dir.path().join(format!("{format:?}{history}.db"));
+                let target = data::Storage::connect(&file).await.unwrap();
+                let counts = cmd(&file)
+                    .import(&target, input.as_bytes())
+                    .await
+                    .unwrap();
+                let expected = views().count().await;
+                assert_eq!(
+                    counts,
+                    Counts {
+                        imported: expected,
+                        skipped: 0,
+                        malformed: 1,
+                    },
+                    "{format:?} history={history}"
+                );
+                let imported = if history {
+                    target.history_stream()
+                } else {
+                    target.views_stream()
+                };
+                assert_eq!(sorted(imported).await, sorted(views()).await);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn checkpoint_resumes_by_host() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("target.db");
+        let checkpoint_file = dir.path().join("checkpoint");
+        let target = data::Storage::connect(&file).await.unwrap();
+        let cmd = Cmd {
+            checkpoint_file: Some(checkpoint_file.clone()),
+            ..cmd(&file)
+        };
+        let mut input = String::new();
+        for (host, link) in [
+            ("a", "/1"),
+            ("a", "/2"),
+            ("b", "/1"),
+            ("b", "/2"),
+            ("b", "/3"),
+        ] {
+            input.push_str(
+                &serde_json::to_string(&view(host, link, &[])).unwrap(),
+            );
+            input.push('\n');
+        }
+        let mut interrupted = input.clone().into_bytes();
+        interrupted.extend(b"\xff\n");
+        assert!(cmd.import(&target, &interrupted[..]).await.is_err());
+        let done = std::fs::read_to_string(&checkpoint_file).unwrap();
+        assert_eq!(done, r#"["a"]"#);
+
+        let counts = cmd.import(&target, input.as_bytes()).await.unwrap();
+        assert_eq!(
+            counts,
+            Counts {
+                imported: 3,
+                skipped: 2,
+                malformed: 0,
+            }
+        );
+        assert!(!checkpoint_file.exists());
+
+        input
+            .push_str(&serde_json::to_string(&view("a", "/3", &[])).unwrap());
+        let error = cmd.import(&target, input.as_bytes()).await.unwrap_err();
+        assert!(error.to_string().contains("not all together"), "{error}");
+    }
+}
@@ -0,0 +1,220 @@
+use std::{fmt, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::data;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default)]
+pub enum SortBy {
+    /// When each was scanned.
+    Scanned,
+    /// Newest commit on any branch. Unreadable repos come first.
+    Commit,
+    /// Local path or remote URL.
+    #[default]
+    Path,
+    /// Number of branches.
+    Branches,
+}
+
+impl From<SortBy> for data::ViewOrder {
+    fn from(sort_by: SortBy) -> Self {
+        match sort_by {
+            SortBy::Scanned => Self::Scanned,
+            SortBy::Commit => Self::Commit,
+            SortBy::Path => Self::Path,
+            SortBy::Branches => Self::Branches,
+        }
+    }
+}
+
+/// A column of the human output.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    /// When it was scanned.
+    Scanned,
+    /// Newest commit on any branch.
+    #[value(alias = "latest_commit")]
+    LatestCommit,
+    /// Number of branches.
+    Branches,
+    /// Number of remotes.
+    Remotes,
+    /// Host which scanned it.
+    Host,
+    /// Local path or remote URL.
+    Path,
+    /// Why it could not be read, on one line.
+    Error,
+}
+
+impl Column {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Scanned => "scanned",
+            Self::LatestCommit => "latest-commit",
+            Self::Branches => "branches",
+            Self::Remotes => "remotes",
+            Self::Host => "host",
+            Self::Path => "path",
+            Self::Error => "error",
+        }
+    }
+
+    fn cell(self, view: &data::View) -> String {
+        let repo = view.repo.as_ref();
+        match self {
+            Self::Scanned => view.scanned_at.to_string(),
+            Self::LatestCommit => {
+                or_dash(repo.and_then(|r| r.latest_commit_time))
+            }
+            Self::Branches => or_dash(repo.map(|r| r.branches.len())),
+            Self::Remotes => or_dash(repo.map(|r| r.remotes.len())),
+            Self::Host => view.host.clone(),
+            Self::Path => view.link.to_string(),
+            Self::Error => or_dash(view.error.as_ref().map(|error| {
+                error.split_whitespace().collect::<Vec<_>>().join(" ")
+            })),
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(
+            self,
+            Self::Scanned
+                | Self::LatestCommit
+                | Self::Branches
+                | Self::Remotes
+        )
+    }
+}
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+
+    /// Columns of the human output, in order, separated by commas.
+    #[clap(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "scanned,latest-commit,branches,host,path"
+    )]
+    columns: Vec<Column>,
+
+    /// What to sort by.
+    #[clap(long, value_enum, default_value_t = SortBy::Path)]
+    sort_by: SortBy,
+
+    /// Sort in descending order, e.g. newest first.
+    #[clap(long, default_value_t = false)]
+    reverse: bool,
+
+    /// List at most this many. All by default.
+    #[clap(long)]
+    limit: Option<u64>,
+
+    /// Skip this many first, e.g. to page through them with `--limit`.
+    #[clap(long, default_value_t = 0)]
+    offset: u64,
+
+    /// List only the repos which could not be read, e.g. with the `error`
+    /// column to see why.
+    #[clap(long, default_value_t = false)]
+    failed: bool,
+}
+
+/// One page of the sorted views, with how many there are in all.
+#[derive(Serialize, Debug)]
+struct Page {
+    total: u64,
+    offset: u64,
+    views: Vec<data::View>,
+}
+
+impl Cmd {
+    /// Lists the latest view of every stored link, local and remote.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let total = storage.count_views(self.failed).await?;
+        let views = storage
+            .views_sorted(
+                self.sort_by.into(),
+                self.reverse,
+                self.failed,
+                self.limit,
+                self.offset,
+            )
+            .await?;
+        let page = Page {
+            total,
+            offset: self.offset,
+            views,
+        };
+        match self.output {
+            super::Output::Human => {
+                print!("{}", table(&self.columns, &page.views));
+                println!(
+                    "shown={} offset={} total={}",
+                    page.views.len(),
+                    page.offset,
+                    page.total
+                );
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&page)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Views as rows of the columns, under a header, with each column as wide
+/// as its widest cell. Numbers are aligned right, the rest left.
+fn table(columns: &[Column], views: &[data::View]) -> String {
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    let rows: Vec<Vec<String>> = std::iter::once(header)
+        .chain(views.iter().map(|view| {
+            columns.iter().map(|column| column.cell(view)).collect()
+        }))
+        .collect();
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(columns)
+            .zip(&widths)
+            .map(|((cell, column), width)| {
+                if column.is_numeric() {
+                    format!("{cell:>width$}")
+                } else {
+                    format!("{cell:<width$}")
+                }
+            })
+            .collect();
+        out.push_str(cells.join(" ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn or_dash<T: fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
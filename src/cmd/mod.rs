@@ -1 +1,151 @@
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+    sync::OnceLock,
+};
+
+use crate::os;
+
+pub mod at_risk;
+pub mod diff;
+pub mod doctor;
+pub mod duplicates;
+pub mod export;
 pub mod find;
+pub mod history;
+pub mod hosts;
+pub mod import;
+pub mod list;
+pub mod remotes;
+pub mod retry;
+pub mod serve;
+pub mod stats;
+pub mod vacuum;
+pub mod verify_db;
+pub mod watch;
+
+/// Format of a command's report on stdout.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Output {
+    #[default]
+    Human,
+    Json,
+}
+
+/// Whether to color human output.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default)]
+pub enum Color {
+    /// Only when stdout is a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static COLOR_ENABLED: OnceLock<bool> = OnceLock::new();
+
+impl Color {
+    /// Decides, once per process, whether human output gets colored.
+    pub fn init(self) {
+        let enabled = match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+                    && io::stdout().is_terminal()
+            }
+        };
+        let _ = COLOR_ENABLED.set(enabled);
+    }
+}
+
+/// Whether names of git dirs are matched regardless of case, as git takes
+/// them on case-insensitive file systems.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NameCase {
+    /// Ignored on macOS and Windows, whose file systems usually ignore it,
+    /// and not elsewhere.
+    #[default]
+    Auto,
+    Sensitive,
+    Insensitive,
+}
+
+impl NameCase {
+    /// Whether names are to be matched regardless of case, on this
+    /// platform.
+    #[must_use]
+    pub fn ignore_case(self) -> bool {
+        match self {
+            Self::Auto => cfg!(any(target_os = "macos", windows)),
+            Self::Sensitive => false,
+            Self::Insensitive => true,
+        }
+    }
+}
+
+/// How to spell the name of this host, which views are stored under.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HostStyle {
+    /// As `hostname` prints it, with or without the domain, depending on
+    /// the system.
+    #[default]
+    Raw,
+    /// Without the domain.
+    Short,
+    /// With the domain, as `hostname -f` prints it.
+    Fqdn,
+}
+
+impl HostStyle {
+    /// The given name, if any, as is, or else the target's in this style.
+    pub async fn hostname(
+        self,
+        name: Option<&str>,
+        target: &os::Target,
+    ) -> anyhow::Result<String> {
+        if let Some(name) = name {
+            return Ok(name.to_string());
+        }
+        match self {
+            Self::Raw => os::hostname(target).await,
+            Self::Short => {
+                let name = os::hostname(target).await?;
+                Ok(name.split('.').next().unwrap_or(&name).to_string())
+            }
+            Self::Fqdn => os::fqdn(target).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Paint {
+    Green,
+    Red,
+    Yellow,
+}
+
+impl Paint {
+    /// Wraps the text in ANSI color codes, if colors are enabled.
+    pub(crate) fn paint<T: fmt::Display>(self, text: T) -> String {
+        if !COLOR_ENABLED.get().copied().unwrap_or(false) {
+            return text.to_string();
+        }
+        let code = match self {
+            Self::Green => 32,
+            Self::Red => 31,
+            Self::Yellow => 33,
+        };
+        format!("\x1b[{code}m{text}\x1b[0m")
+    }
+
+    /// Colors a line by its leading `+`, `-` or `~`.
+    pub(crate) fn diff_line(line: &str) -> String {
+        match line.chars().next() {
+            Some('+') => Self::Green.paint(line),
+            Some('-') => Self::Red.paint(line),
+            Some('~') => Self::Yellow.paint(line),
+            _ => line.to_string(),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Serialize;
+
+use super::Paint;
+use crate::{data, git};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+
+    /// Only remotes on this host, e.g. "gitlab.internal", however they're
+    /// addressed.
+    #[clap(long)]
+    host: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    /// Was readable when last probed.
+    Ok,
+    /// Was unreadable when last probed.
+    Err,
+    /// Was never probed.
+    Unknown,
+}
+
+#[derive(Serialize, Debug)]
+struct Remote {
+    url: String,
+    /// Number of local repos with this URL among their remotes.
+    repos: usize,
+    status: Status,
+}
+
+impl Cmd {
+    /// Parsed here, rather than read from the repo, to cover views stored
+    /// before remote URLs were parsed.
+    fn on_host(&self, url: &str) -> bool {
+        self.host.as_ref().is_none_or(|host| {
+            git::parse_remote_url(url)
+                .host
+                .is_some_and(|h| h.eq_ignore_ascii_case(host))
+        })
+    }
+
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let mut remotes: BTreeMap<String, Remote> = BTreeMap::new();
+        let views = storage.views().await?;
+        for view in &views {
+            if let (data::Link::Fs { .. }, Some(repo)) =
+                (&view.link, &view.repo)
+            {
+                for url in
+                    repo.remotes.values().filter(|url| self.on_host(url))
+                {
+                    remotes
+                        .entry(url.clone())
+                        .or_insert_with(|| Remote {
+                            url: url.clone(),
+                            repos: 0,
+                            status: Status::Unknown,
+                        })
+                        .repos += 1;
+                }
+            }
+        }
+        for view in &views {
+            if let data::Link::Net { url } = &view.link {
+                if let Some(remote) = remotes.get_mut(url) {
+                    remote.status = match view.reachable {
+                        Some(true) => Status::Ok,
+                        Some(false) => Status::Err,
+                        None => Status::Unknown,
+                    };
+                }
+            }
+        }
+        let remotes: Vec<Remote> = remotes.into_values().collect();
+        match self.output {
+            super::Output::Human => {
+                for Remote { url, repos, status } in &remotes {
+                    let status = match status {
+                        Status::Ok => Paint::Green.paint("ok     "),
+                        Status::Err => Paint::Red.paint("err    "),
+                        Status::Unknown => Paint::Yellow.paint("unknown"),
+                    };
+                    println!("{repos:>8} {status} {url}");
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&remotes)?);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use futures::{stream, StreamExt};
+
+use super::Paint;
+use crate::{data, git, redact};
+
+#[derive(clap::Args, Debug, Clone)]
+#[clap(group(
+    clap::ArgGroup::new("which").required(true).args(["timeouts", "failed"])
+))]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Probe again the remotes which last timed out.
+    #[clap(long)]
+    timeouts: bool,
+
+    /// Probe again the remotes which last failed to be read, for any
+    /// reason.
+    #[clap(long)]
+    failed: bool,
+
+    /// Seconds to wait for a connection to an SSH remote, e.g. longer
+    /// than the scan which timed out did.
+    #[clap(long, default_value_t = 30)]
+    ssh_connect_timeout: u64,
+
+    /// Store views under this host name rather than looking it up. Only
+    /// remotes probed from this host are retried.
+    #[clap(long)]
+    host_name: Option<String>,
+
+    /// How to spell the looked up host name.
+    #[clap(long, value_enum, default_value_t = super::HostStyle::Raw)]
+    host_style: super::HostStyle,
+
+    /// Probe at most this many remotes at once, each being a git process,
+    /// so thousands of failures don't run this host out of processes or
+    /// file descriptors.
+    #[clap(long, default_value = "16")]
+    max_concurrency: NonZeroUsize,
+}
+
+impl Cmd {
+    /// Probes again, from this host, the remotes which this host last
+    /// failed to read, and stores the new views alongside the old ones.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let host = self
+            .host_style
+            .hostname(self.host_name.as_deref(), &crate::os::Target::Local)
+            .await?;
+        let storage = data::Storage::connect(&self.db_file).await?;
+        let links: Vec<data::Link> = storage
+            .views()
+            .await?
+            .into_iter()
+            .filter(|view| view.host == host)
+            .filter(|view| matches!(view.link, data::Link::Net { .. }))
+            .filter(|view| match view.failure {
+                Some(data::RemoteFailure::Timeout) => true,
+                Some(_) => self.failed,
+                None => false,
+            })
+            .map(|view| view.link)
+            .collect();
+        tracing::info!(n = links.len(), "Retrying remotes.");
+        let run_opts = git::RunOpts::default();
+        let remote_opts = git::RemoteOpts {
+            ssh_connect_timeout: Some(self.ssh_connect_timeout),
+            ..git::RemoteOpts::default()
+        };
+        let (exhausted, views): (Vec<data::View>, Vec<data::View>) =
+            stream::iter(&links)
+                .map(|link| git::view(&run_opts, &host, link, &remote_opts))
+                .buffer_unordered(self.max_concurrency.get())
+                .collect::<Vec<data::View>>()
+                .await
+                .into_iter()
+                .partition(data::View::is_unread_for_lack_of_resources);
+        if !exhausted.is_empty() {
+            tracing::warn!(
+                n = exhausted.len(),
+                "Out of processes or file descriptors. Not storing those \
+                remotes. Retry them with a lower --max-concurrency."
+            );
+        }
+        for view in &views {
+            let status = match view.failure {
+                None => Paint::Green.paint("ok"),
+                Some(failure) => Paint::Red.paint(format!("{failure:?}")),
+            };
+            println!(
+                "{status} {}",
+                redact::credentials(&view.link.to_string())
+            );
+        }
+        storage.store_views(&views).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use std::{net::IpAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use serde::Serialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpListener,
+};
+use tracing::{info_span, Instrument};
+
+use crate::data;
+
+/// Requests with larger headers than this are rejected.
+const MAX_HEAD_LEN: usize = 8 * 1024;
+
+/// Clients which take longer than this to send the headers are dropped,
+/// so idle ones don't hold on to a task and a socket forever.
+const HEAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Address to listen on. Only reachable from this machine by default.
+    #[clap(short, long, default_value = "127.0.0.1")]
+    addr: IpAddr,
+
+    /// Port to listen on.
+    #[clap(short, long, default_value_t = 8080)]
+    port: u16,
+}
+
+impl Cmd {
+    /// Serves read-only JSON endpoints:
+    ///
+    /// - `/views` - latest view of every link
+    /// - `/stats` - same as the `stats` command
+    /// - `/hosts` - hosts with their view counts
+    /// - `/search?q=<text>` - views whose link or remote URLs contain text
+    ///
+    /// `/views` and `/search` take `limit` and `offset` to page through
+    /// them, and report how many there are in all in `X-Total-Count`.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage =
+            Arc::new(data::Storage::open_readonly(&self.db_file).await?);
+        let listener = TcpListener::bind((self.addr, self.port)).await?;
+        tracing::info!(addr = ?listener.local_addr()?, "Listening.");
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let storage = storage.clone();
+            tokio::spawn(
+                async move {
+                    if let Err(error) =
+                        handle(stream, &storage, HEAD_TIMEOUT).await
+                    {
+                        tracing::error!(?error, "Failed to handle request.");
+                    }
+                }
+                .instrument(info_span!("request", ?peer)),
+            );
+        }
+    }
+}
+
+async fn handle<S>(
+    mut stream: S,
+    storage: &data::Storage,
+    head_timeout: Duration,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Ok(head) =
+        tokio::time::timeout(head_timeout, read_head(&mut stream)).await
+    else {
+        return respond(&mut stream, "408 Request Timeout", "").await;
+    };
+    let head = head?;
+    if head.len() > MAX_HEAD_LEN {
+        return respond(
+            &mut stream,
+            "431 Request Header Fields Too Large",
+            "",
+        )
+        .await;
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or("").split(' ');
+    let method = request_line.next().unwrap_or("");
+    let target = request_line.next().unwrap_or("");
+    tracing::debug!(method, target, "Request.");
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "").await;
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let limit = param(query, "limit").and_then(|n| n.parse().ok());
+    let offset = param(query, "offset").and_then(|n| n.parse().ok());
+    let body = match path {
+        "/views" => {
+            let views = storage.views().await?;
+            return respond_page(&mut stream, views, limit, offset).await;
+        }
+        "/stats" => json(&super::stats::Stats::from_views(
+            storage.views().await?,
+            10,
+            90,
+            2,
+        ))?,
+        "/hosts" => json(&storage.hosts().await?)?,
+        "/search" => {
+            let q = param(query, "q").unwrap_or_default();
+            let views: Vec<data::View> = storage
+                .views()
+                .await?
+                .into_iter()
+                .filter(|view| matches(view, &q))
+                .collect();
+            return respond_page(&mut stream, views, limit, offset).await;
+        }
+        _ => return respond(&mut stream, "404 Not Found", "").await,
+    };
+    respond(&mut stream, "200 OK", &body).await
+}
+
+/// Reads up to the end of the headers, or just past [`MAX_HEAD_LEN`], or
+/// until the client stops sending.
+async fn read_head<S: AsyncRead + Unpin>(
+    stream: &mut S,
+) -> anyhow::Result<Vec<u8>> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n")
+        && head.len() <= MAX_HEAD_LEN
+    {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+    Ok(head)
+}
+
+/// Value of the first `key` in a query string, decoded.
+fn param(query: &str, key: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| percent_decode(value))
+}
+
+/// Responds with at most `limit` of the views, after skipping `offset`,
+/// and the number of views in all.
+async fn respond_page(
+    stream: &mut (impl AsyncWrite + Unpin),
+    views: Vec<data::View>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> anyhow::Result<()> {
+    let total = views.len();
+    let page: Vec<data::View> = views
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect();
+    let body = json(&page)?;
+    let total = total.to_string();
+    respond_with(stream, "200 OK", &[("X-Total-Count", &total)], &body).await
+}
+
+fn json<T: Serialize>(value: &T) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(value)?)
+}
+
+fn matches(view: &data::View, q: &str) -> bool {
+    view.link.to_string().contains(q)
+        || view
+            .repo
+            .iter()
+            .flat_map(|repo| repo.remotes.values())
+            .any(|url| url.contains(q))
+}
+
+async fn respond(
+    stream: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    body: &str,
+) -> anyhow::Result<()> {
+    respond_with(stream, status, &[], body).await
+}
+
+async fn respond_with(
+    stream: &mut (impl AsyncWrite + Unpin),
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &str,
+) -> anyhow::Result<()> {
+    let headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect();
+    let head = format!(
+        "HTTP/1.1 {status}\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: {}\r\n\
+        {headers}\
+        Connection: close\r\n\
+        \r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Decodes `%XX` escapes and `+` as space, as in query strings.
+fn percent_decode(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = input.by_ref().take(2).collect();
+                // Exactly two digits, since from_str_radix also takes one,
+                // or a sign.
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .filter(|hex| {
+                        hex.len() == 2
+                            && hex.bytes().all(|b| b.is_ascii_hexdigit())
+                    })
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(decoded) => bytes.push(decoded),
+                    None => {
+                        bytes.push(b'%');
+                        bytes.extend(hex);
+                    }
+                }
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::data::tests::view;
+
+    async fn storage(dir: &std::path::Path) -> data::Storage {
+        let file = dir.join("test.db");
+        let storage = data::Storage::connect(&file).await.unwrap();
+        storage
+            .store_views(&[
+                view("a", "/src/one", &[("origin", "https://x.org/me/one")]),
+                view("a", "/src/two", &[]),
+                view("b", "/src/three", &[("up", "git@y.org:me/three")]),
+            ])
+            .await
+            .unwrap();
+        data::Storage::open_readonly(&file).await.unwrap()
+    }
+
+    /// Head and body of the response to the request.
+    async fn request(
+        storage: &data::Storage,
+        request: &str,
+    ) -> (String, String) {
+        let (mut client, server) = tokio::io::duplex(64 * 1024);
+        client.write_all(request.as_bytes()).await.unwrap();
+        handle(server, storage, HEAD_TIMEOUT).await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.to_string(), body.to_string())
+    }
+
+    async fn get(storage: &data::Storage, target: &str) -> (String, String) {
+        request(storage, &format!("GET {target} HTTP/1.1\r\n\r\n")).await
+    }
+
+    #[tokio::test]
+    async fn endpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = storage(dir.path()).await;
+
+        let (head, body) = get(&storage, "/views").await;
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+        assert!(head.contains("X-Total-Count: 3"), "{head}");
+        let views: Vec<data::View> = serde_json::from_str(&body).unwrap();
+        assert_eq!(views.len(), 3);
+
+        let (head, body) = get(&storage, "/views?limit=1&offset=2").await;
+        assert!(head.contains("X-Total-Count: 3"), "{head}");
+        let views: Vec<data::View> = serde_json::from_str(&body).unwrap();
+        assert_eq!(views.len(), 1);
+
+        let (head, body) = get(&storage, "/stats").await;
+        assert!(head.starts_with("HTTP/1.1 200 OK"), "{head}");
+        let stats: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(stats.is_object(), "{body}");
+
+        let (_, body) = get(&storage, "/hosts").await;
+        let hosts: Vec<(String, i64)> = serde_json::from_str(&body).unwrap();
+        assert_eq!(hosts, [("a".to_string(), 2), ("b".to_string(), 1)]);
+
+        let (head, body) = get(&storage, "/search?q=y.org%3Ame").await;
+        assert!(head.contains("X-Total-Count: 1"), "{head}");
+        let views: Vec<data::View> = serde_json::from_str(&body).unwrap();
+        assert_eq!(views[0].link.to_string(), "/src/three");
+
+        let (head, _) = get(&storage, "/nope").await;
+        assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+        let (head, _) =
+            request(&storage, "POST /views HTTP/1.1\r\n\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 405"), "{head}");
+    }
+
+    #[tokio::test]
+    async fn oversized_and_idle_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = storage(dir.path()).await;
+
+        let target = format!("/views?q={}", "x".repeat(MAX_HEAD_LEN));
+        let (head, _) = get(&storage, &target).await;
+        assert!(head.starts_with("HTTP/1.1 431"), "{head}");
+
+        // Connected, but never sends anything.
+        let (mut client, server) = tokio::io::duplex(1024);
+        handle(server, &storage, Duration::from_millis(10))
+            .await
+            .unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 408"), "{response}");
+    }
+
+    #[test]
+    fn percent_decode_edge_cases() {
+        let cases = [
+            ("plain", "plain"),
+            ("a+b", "a b"),
+            ("a%20b", "a b"),
+            ("%2Fsrc%2fone", "/src/one"),
+            ("%e2%82%ac", "\u{20ac}"),
+            ("100%", "100%"),
+            ("%4", "%4"),
+            ("%+1", "%+1"),
+            ("%zz", "%zz"),
+            ("%%41", "%%41"),
+            ("%ff", "\u{fffd}"),
+            ("", ""),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(percent_decode(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn param_takes_first_decoded() {
+        assert_eq!(param("q=a%20b&q=c", "q").as_deref(), Some("a b"));
+        assert_eq!(param("limit=5", "q"), None);
+        assert_eq!(param("q", "q"), None);
+        assert_eq!(param("q=", "q").as_deref(), Some(""));
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
+
+use serde::Serialize;
+
+use super::Paint;
+use crate::{data, os};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+
+    /// How many of the slowest remotes to list.
+    #[clap(short, long, default_value_t = 10)]
+    top: usize,
+
+    /// Days after which a local repo not fetched from any of its remotes
+    /// is counted as stale.
+    #[clap(long, default_value_t = 90)]
+    stale_days: u64,
+
+    /// Years after which a local repo with no newer commit on any branch is
+    /// counted as untouched.
+    #[clap(long, default_value_t = 2)]
+    untouched_years: u64,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct Stats {
+    hosts: usize,
+    locals: usize,
+    locals_unreadable: usize,
+    /// Non-bare local repos with HEAD pointing at a commit, not a branch.
+    locals_head_detached: usize,
+    /// Local repos whose remotes were all last fetched from longer ago
+    /// than the stale days.
+    locals_stale: usize,
+    /// Local repos whose newest commit is older than the untouched years.
+    locals_untouched: usize,
+    /// Local repos borrowing objects from elsewhere, via alternates.
+    locals_with_alternates: usize,
+    /// Local repos with history cut short, so without reliable roots.
+    locals_shallow: usize,
+    /// Local repos with hooks installed.
+    locals_with_hooks: usize,
+    remotes_ok: usize,
+    remotes_err: usize,
+    remotes_err_by_failure: BTreeMap<data::RemoteFailure, usize>,
+    /// Remote URLs with their probe time in milliseconds, slowest first.
+    slowest_remotes: Vec<(String, u64)>,
+}
+
+impl Stats {
+    /// Summarizes the views, keeping the `top` slowest remotes.
+    #[must_use]
+    pub fn from_views(
+        views: Vec<data::View>,
+        top: usize,
+        stale_days: u64,
+        untouched_years: u64,
+    ) -> Self {
+        let now = os::timestamp();
+        let ago = |secs: u64| {
+            now.saturating_sub(i64::try_from(secs).unwrap_or(i64::MAX))
+        };
+        let stale_before = ago(stale_days.saturating_mul(24 * 60 * 60));
+        let untouched_before =
+            ago(untouched_years.saturating_mul(365 * 24 * 60 * 60));
+        let mut stats = Self::default();
+        let mut hosts = HashSet::new();
+        for view in views {
+            hosts.insert(view.host);
+            match (&view.link, &view.repo) {
+                (data::Link::Fs { .. }, Some(repo)) => {
+                    stats.locals += 1;
+                    if repo.shallow {
+                        stats.locals_shallow += 1;
+                    }
+                    if !repo.hooks.is_empty() {
+                        stats.locals_with_hooks += 1;
+                    }
+                    if !repo.alternates.is_empty() {
+                        stats.locals_with_alternates += 1;
+                    }
+                    if repo.head_detached == Some(true) {
+                        stats.locals_head_detached += 1;
+                    }
+                    if repo
+                        .last_fetched
+                        .values()
+                        .max()
+                        .is_some_and(|time| *time < stale_before)
+                    {
+                        stats.locals_stale += 1;
+                    }
+                    if repo
+                        .latest_commit_time
+                        .is_some_and(|time| time < untouched_before)
+                    {
+                        stats.locals_untouched += 1;
+                    }
+                }
+                (data::Link::Fs { .. }, None) => {
+                    stats.locals += 1;
+                    stats.locals_unreadable += 1;
+                }
+                (data::Link::Net { url }, repo) => {
+                    if repo.is_some() {
+                        stats.remotes_ok += 1;
+                    } else {
+                        stats.remotes_err += 1;
+                    }
+                    if let Some(failure) = view.failure {
+                        *stats
+                            .remotes_err_by_failure
+                            .entry(failure)
+                            .or_default() += 1;
+                    }
+                    if let Some(probe_ms) = view.probe_ms {
+                        stats.slowest_remotes.push((url.clone(), probe_ms));
+                    }
+                }
+            }
+        }
+        stats.hosts = hosts.len();
+        stats.slowest_remotes.sort_by(|(_, a), (_, b)| b.cmp(a));
+        stats.slowest_remotes.truncate(top);
+        stats
+    }
+}
+
+impl Cmd {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let stats = Stats::from_views(
+            storage.views().await?,
+            self.top,
+            self.stale_days,
+            self.untouched_years,
+        );
+        match self.output {
+            super::Output::Human => {
+                let rows = [
+                    ("hosts", stats.hosts.to_string()),
+                    ("locals", stats.locals.to_string()),
+                    (
+                        "locals_unreadable",
+                        stats.locals_unreadable.to_string(),
+                    ),
+                    ("locals_stale", Paint::Yellow.paint(stats.locals_stale)),
+                    (
+                        "locals_untouched",
+                        Paint::Yellow.paint(stats.locals_untouched),
+                    ),
+                    (
+                        "locals_with_alternates",
+                        Paint::Yellow.paint(stats.locals_with_alternates),
+                    ),
+                    (
+                        "locals_shallow",
+                        Paint::Yellow.paint(stats.locals_shallow),
+                    ),
+                    (
+                        "locals_with_hooks",
+                        Paint::Yellow.paint(stats.locals_with_hooks),
+                    ),
+                    (
+                        "locals_head_detached",
+                        Paint::Yellow.paint(stats.locals_head_detached),
+                    ),
+                    ("remotes_ok", Paint::Green.paint(stats.remotes_ok)),
+                    ("remotes_err", Paint::Red.paint(stats.remotes_err)),
+                ];
+                for (name, value) in rows {
+                    println!("{name:<22} {value}");
+                }
+                for (failure, count) in &stats.remotes_err_by_failure {
+                    println!("    {count:>8} {failure:?}");
+                }
+                if !stats.slowest_remotes.is_empty() {
+                    println!("slowest_remotes");
+                    for (url, probe_ms) in &stats.slowest_remotes {
+                        println!("    {probe_ms:>8}ms {url}");
+                    }
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            }
+        }
+        Ok(())
+    }
+}
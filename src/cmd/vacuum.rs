@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+}
+
+#[derive(Serialize, Debug)]
+struct Sizes {
+    before_bytes: u64,
+    after_bytes: u64,
+}
+
+impl Cmd {
+    /// Reclaims the space left in the database file by replaced and
+    /// deleted rows.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        // Rather than creating an empty one to vacuum.
+        if !self.db_file.try_exists()? {
+            anyhow::bail!("Database file not found: {:?}", self.db_file);
+        }
+        let before_bytes = size(&self.db_file).await?;
+        let storage = data::Storage::connect(&self.db_file).await?;
+        storage.vacuum().await?;
+        let after_bytes = size(&self.db_file).await?;
+        let sizes = Sizes {
+            before_bytes,
+            after_bytes,
+        };
+        match self.output {
+            super::Output::Human => {
+                println!("before_bytes {}", sizes.before_bytes);
+                println!("after_bytes  {}", sizes.after_bytes);
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&sizes)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn size(file: &Path) -> anyhow::Result<u64> {
+    Ok(tokio::fs::metadata(file).await?.len())
+}
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+
+use super::Paint;
+use crate::data;
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Output format.
+    #[clap(short, long, value_enum, default_value_t = super::Output::Human)]
+    output: super::Output,
+}
+
+impl Cmd {
+    /// Fails if any row is unreadable.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let storage = data::Storage::open_readonly(&self.db_file).await?;
+        let checks = storage.check().await?;
+        match self.output {
+            super::Output::Human => {
+                for check in &checks {
+                    let unreadable = check.unreadable.len();
+                    let unreadable = if unreadable == 0 {
+                        Paint::Green.paint(unreadable)
+                    } else {
+                        Paint::Red.paint(unreadable)
+                    };
+                    println!(
+                        "{:<8} rows={} unreadable={unreadable}",
+                        check.table, check.rows
+                    );
+                    for (id, error) in &check.unreadable {
+                        println!("    id={id} {error}");
+                    }
+                }
+            }
+            super::Output::Json => {
+                println!("{}", serde_json::to_string_pretty(&checks)?);
+            }
+        }
+        let unreadable: usize =
+            checks.iter().map(|check| check.unreadable.len()).sum();
+        if unreadable > 0 {
+            bail!("Found {unreadable} unreadable rows.");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+
+use crate::{data, git};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct Cmd {
+    /// Database file.
+    #[clap(short, long, default_value = "git-tracker.db")]
+    db_file: PathBuf,
+
+    /// Use the database file even if it doesn't look like one of ours,
+    /// adding our tables to it.
+    #[clap(long, default_value_t = false)]
+    force: bool,
+
+    /// Follow symbollic links.
+    #[clap(short, long, default_value_t = false)]
+    follow: bool,
+
+    /// Ignore this path when searching for repos.
+    #[clap(short, long)]
+    ignore_paths: Vec<PathBuf>,
+
+    /// Whether git dir names match regardless of case.
+    #[clap(long, value_enum, default_value_t = super::NameCase::Auto)]
+    name_case: super::NameCase,
+
+    /// Seconds between walks of the search paths.
+    #[clap(long, default_value_t = 10)]
+    interval: u64,
+
+    /// Seconds a new repo must have existed before it is read, so that
+    /// one which is still being cloned isn't read half-way.
+    #[clap(long, default_value_t = 30)]
+    settle: u64,
+
+    /// Store views under this host name rather than looking it up, e.g.
+    /// to keep it stable across environments.
+    #[clap(long)]
+    host_name: Option<String>,
+
+    /// How to spell the looked up host name.
+    #[clap(long, value_enum, default_value_t = super::HostStyle::Raw)]
+    host_style: super::HostStyle,
+
+    /// Local paths to watch for new git repos.
+    search_paths: Vec<PathBuf>,
+}
+
+impl Cmd {
+    /// Polls the search paths, rather than subscribing to filesystem
+    /// events, reusing the same walk as `find`. Repos which exist at
+    /// startup are considered known and skipped.
+    pub async fn run(&self) -> anyhow::Result<()> {
+        let ignore_case = self.name_case.ignore_case();
+        let ignore_paths: HashSet<PathBuf> =
+            self.ignore_paths.iter().cloned().collect();
+        let mut search_paths = Vec::new();
+        for path in &self.search_paths {
+            let path = path
+                .canonicalize()
+                .context(format!("Invalid local path={path:?}"))?;
+            search_paths.push(path);
+        }
+        let host = self
+            .host_style
+            .hostname(self.host_name.as_deref(), &crate::os::Target::Local)
+            .await?;
+        let storage = if self.force {
+            data::Storage::connect_force(&self.db_file).await?
+        } else {
+            data::Storage::connect(&self.db_file).await?
+        };
+        let run_opts = git::RunOpts::default();
+        let remote_opts = git::RemoteOpts::default();
+        let settle = Duration::from_secs(self.settle);
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.interval));
+        let mut known: HashSet<PathBuf> =
+            walk(&search_paths, ignore_case, self.follow, &ignore_paths)
+                .await?
+                .into_iter()
+                .collect();
+        tracing::info!(known = known.len(), "Watching for new repos.");
+        let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+        loop {
+            interval.tick().await;
+            for dir in
+                walk(&search_paths, ignore_case, self.follow, &ignore_paths)
+                    .await?
+            {
+                if !known.contains(&dir) {
+                    pending.entry(dir).or_insert_with(Instant::now);
+                }
+            }
+            let settled: Vec<PathBuf> = pending
+                .iter()
+                .filter(|(_, first_seen)| first_seen.elapsed() >= settle)
+                .map(|(dir, _)| dir.clone())
+                .collect();
+            let mut views = Vec::new();
+            for dir in settled {
+                pending.remove(&dir);
+                if !git::is_repo(&run_opts, &dir).await {
+                    continue;
+                }
+                tracing::info!(?dir, "Found new repo.");
+                let link = data::Link::Fs { dir: dir.clone() };
+                views.push(
+                    git::view(&run_opts, &host, &link, &remote_opts).await,
+                );
+                known.insert(dir);
+            }
+            if !views.is_empty() {
+                match storage.store_views(&views[..]).await {
+                    Ok(()) => {
+                        tracing::info!(
+                            n = views.len(),
+                            "Views store succeeded."
+                        );
+                    }
+                    Err(error) => {
+                        tracing::error!(?error, "Views store failed.");
+                    }
+                }
+            }
+        }
+    }
+}
+
+async fn walk(
+    search_paths: &[PathBuf],
+    ignore_case: bool,
+    follow: bool,
+    ignore_paths: &HashSet<PathBuf>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let search_paths = search_paths.to_vec();
+    let ignore_paths = ignore_paths.clone();
+    let dirs = tokio::task::spawn_blocking(move || {
+        search_paths
+            .iter()
+            .flat_map(|path| {
+                crate::fs::find_dirs(
+                    path,
+                    &[".git".to_string()],
+                    ignore_case,
+                    follow,
+                    false,
+                    &ignore_paths,
+                    false,
+                )
+            })
+            .collect()
+    })
+    .await?;
+    Ok(dirs)
+}
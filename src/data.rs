@@ -1,38 +1,463 @@
+//! The one set of types for repos and their views, which the git layer
+//! reads into and storage persists as JSON, so the two can't drift apart.
+
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
     path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
 };
 
+use futures::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
 use serde::{Deserialize, Serialize};
-use sqlx::Executor;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteRow},
+    Executor, Row,
+};
 use tokio::fs;
 
-const MIGRATIONS: [&str; 1] = [include_str!("../migrations/0_data.sql")];
+use crate::redact;
+
+const MIGRATIONS: [&str; 6] = [
+    include_str!("../migrations/0_data.sql"),
+    include_str!("../migrations/1_history.sql"),
+    include_str!("../migrations/2_probe_ms.sql"),
+    include_str!("../migrations/3_failure.sql"),
+    include_str!("../migrations/4_reachable.sql"),
+    include_str!("../migrations/5_error.sql"),
+];
+
+/// How long a connection waits on another's write before failing with
+/// "database is locked". Longer than sqlx's 5 seconds, since batches of
+/// views are stored concurrently.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
 
-#[derive(Debug)]
+/// Times a batch of views is tried, should the database stay locked for
+/// longer than [`BUSY_TIMEOUT`].
+const STORE_ATTEMPTS: u32 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct View {
     pub host: String,
     pub link: Link,
     pub repo: Option<Repo>,
+    /// Seconds since the Unix epoch at which the repo was read.
+    pub scanned_at: i64,
+    /// Milliseconds it took to read a remote repo. `None` for local ones.
+    pub probe_ms: Option<u64>,
+    /// Why a remote repo could not be read. `None` for local ones.
+    pub failure: Option<RemoteFailure>,
+    /// Whether a remote repo could be read when probed. `None` for local
+    /// ones, so a failed probe is told apart from none at all.
+    pub reachable: Option<bool>,
+    /// Why the repo could not be read, local or remote, as the full error
+    /// chain with credentials redacted. `None` if it was read.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// Why a remote repo could not be read.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+)]
+pub enum RemoteFailure {
+    AuthRequired,
+    NotFound,
+    NetworkUnreachable,
+    Timeout,
+    /// This host ran out of processes or file descriptors, so the remote
+    /// itself may well be fine.
+    ResourceExhausted,
+    Other,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Hash, Eq, PartialEq)]
+#[derive(
+    Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Ord, PartialOrd,
+)]
 pub enum Link {
     Fs { dir: PathBuf },
     Net { url: String },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Redacts credentials from URLs, since links end up in logs.
+impl fmt::Debug for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fs { dir } => {
+                f.debug_struct("Fs").field("dir", dir).finish()
+            }
+            Self::Net { url } => f
+                .debug_struct("Net")
+                .field("url", &redact::credentials(url))
+                .finish(),
+        }
+    }
+}
+
+impl fmt::Display for Link {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fs { dir } => write!(f, "{}", dir.display()),
+            Self::Net { url } => write!(f, "{url}"),
+        }
+    }
+}
+
+impl FromStr for Link {
+    type Err = anyhow::Error;
+
+    /// URLs (`scheme://...`) and scp-like addresses (`user@host:path`)
+    /// are remote, anything else is a local path.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_url = s.contains("://")
+            || s.split_once(':')
+                .is_some_and(|(prefix, _)| prefix.contains('@'));
+        if is_url {
+            Ok(Self::Net { url: s.to_string() })
+        } else {
+            Ok(Self::Fs {
+                dir: PathBuf::from(s),
+            })
+        }
+    }
+}
+
+impl View {
+    /// Whether the remote went unread for lack of processes or file
+    /// descriptors here, which says nothing about it, so the view isn't to
+    /// be stored.
+    #[must_use]
+    pub fn is_unread_for_lack_of_resources(&self) -> bool {
+        self.failure == Some(RemoteFailure::ResourceExhausted)
+    }
+}
+
+impl sqlx::FromRow<'_, SqliteRow> for View {
+    fn from_row(row: &SqliteRow) -> sqlx::Result<Self> {
+        fn decode<E>(error: E) -> sqlx::Error
+        where
+            E: std::error::Error + Send + Sync + 'static,
+        {
+            sqlx::Error::Decode(Box::new(error))
+        }
+
+        /// Columns added by later migrations are missing from databases
+        /// opened without applying them.
+        fn try_get_added<'r, T>(
+            row: &'r SqliteRow,
+            column: &str,
+        ) -> sqlx::Result<Option<T>>
+        where
+            T: sqlx::Decode<'r, sqlx::Sqlite> + sqlx::Type<sqlx::Sqlite>,
+        {
+            match row.try_get(column) {
+                Err(sqlx::Error::ColumnNotFound(_)) => Ok(None),
+                result => result,
+            }
+        }
+
+        let link: String = row.try_get("link")?;
+        let repo: Option<String> = row.try_get("repo")?;
+        let probe_ms: Option<i64> = try_get_added(row, "probe_ms")?;
+        let failure: Option<String> = try_get_added(row, "failure")?;
+        let reachable: Option<bool> = try_get_added(row, "reachable")?;
+        let error: Option<String> = try_get_added(row, "error")?;
+        // As the migration adding it defaults it to.
+        let scanned_at: i64 = try_get_added(row, "scanned_at")?.unwrap_or(0);
+        Ok(Self {
+            host: row.try_get("host")?,
+            link: serde_json::from_str(&link).map_err(decode)?,
+            repo: repo
+                .map(|repo| serde_json::from_str::<Option<Repo>>(&repo))
+                .transpose()
+                .map_err(decode)?
+                .flatten(),
+            scanned_at,
+            probe_ms: probe_ms
+                .map(u64::try_from)
+                .transpose()
+                .map_err(decode)?,
+            failure: failure
+                .map(|failure| serde_json::from_str(&failure))
+                .transpose()
+                .map_err(decode)?,
+            reachable,
+            error,
+        })
+    }
+}
+
+/// What to sort views by, in [`Storage::views_sorted`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewOrder {
+    /// When the view was scanned.
+    Scanned,
+    /// [`Repo::latest_commit_time`], which unreadable repos lack.
+    Commit,
+    /// The link: local paths, then remote URLs.
+    Path,
+    /// Number of branches, 0 for unreadable repos.
+    Branches,
+}
+
+impl ViewOrder {
+    /// Expression over a row of the views table to sort by.
+    fn sql(self) -> &'static str {
+        match self {
+            Self::Scanned => "scanned_at",
+            Self::Commit => "json_extract(repo, '$.latest_commit_time')",
+            Self::Path => "link",
+            Self::Branches => {
+                "(SELECT COUNT(*) FROM json_each(repo, '$.branches'))"
+            }
+        }
+    }
+}
+
+/// Outcome of [`Storage::check`] for one table.
+#[derive(Serialize, Debug)]
+pub struct TableCheck {
+    pub table: &'static str,
+    pub rows: usize,
+    /// Ids of rows which failed to decode, with why.
+    pub unreadable: Vec<(i64, String)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Branch {
     pub roots: HashSet<String>,
     pub leaf: String,
+    /// Committer date of the leaf, in seconds since the Unix epoch.
+    #[serde(default)]
+    pub committed_at: Option<i64>,
+    /// Short name of the tracked branch, e.g. `origin/master`.
+    #[serde(default)]
+    pub upstream: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    /// What the tag ref points at: the tag object if annotated, else the
+    /// commit.
+    pub object: String,
+    pub annotated: bool,
+}
+
+/// Parts of a remote URL, as parsed by [`crate::git::parse_remote_url`],
+/// e.g. for "git@github.com:me/repo.git": "ssh", "github.com", "me" and
+/// "repo". Users, passwords and ports are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    pub scheme: String,
+    /// Lowercased. `None` for local paths.
+    pub host: Option<String>,
+    /// Path leading to the repo, e.g. user, org, or nested groups as in
+    /// "group/subgroup". `None` if the repo is at the top.
+    pub owner: Option<String>,
+    /// Last path segment, without any `.git` suffix.
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Repo {
     pub description: Option<String>,
     pub remotes: HashMap<String, String>,
+    /// Parts of each remote's URL, by name, as in [`Self::remotes`].
+    #[serde(default)]
+    pub remote_urls: HashMap<String, RemoteUrl>,
     pub branches: HashMap<String, Branch>,
+    /// Roots of all refs. Only recorded by identity-only scans, which
+    /// leave out everything else.
+    #[serde(default)]
+    pub roots: HashSet<String>,
+    /// Whether only the identity was read, roots and whether shallow, so
+    /// remotes, branches and the rest are unknown rather than none.
+    #[serde(default)]
+    pub identity_only: bool,
+    /// Whether HEAD points at a commit rather than a branch. `None` for
+    /// bare repos, which have no checkout, and where not read.
+    #[serde(default)]
+    pub head_detached: Option<bool>,
+    /// Seconds since the Unix epoch at which each remote, by name, was
+    /// last fetched from. Only read for local repos.
+    #[serde(default)]
+    pub last_fetched: HashMap<String, i64>,
+    /// Object stores borrowed from, which make the repo fragile to move.
+    /// Only read for local repos.
+    #[serde(default)]
+    pub alternates: Vec<PathBuf>,
+    /// Names of the hooks installed in the hooks dir, which run on their
+    /// own on e.g. commit, so are a risk in repos on shared machines.
+    /// Only read for local repos, and not from `core.hooksPath`.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+    /// Whether only the roots were read, since the repo was too large to
+    /// read the rest of in reasonable time.
+    #[serde(default)]
+    pub skipped_too_large: bool,
+    /// Committer date of the newest branch leaf, in seconds since the Unix
+    /// epoch, as a measure of how alive the repo is. `None` without
+    /// branches, e.g. in empty repos.
+    #[serde(default)]
+    pub latest_commit_time: Option<i64>,
+    /// Whether history is cut short, as by `clone --depth`, so the commits
+    /// it starts at aren't the real roots. Branch roots are then left out.
+    #[serde(default)]
+    pub shallow: bool,
+    /// Tags by name, e.g. releases. `None` where not read, as by
+    /// identity-only scans and before tags were recorded.
+    #[serde(default)]
+    pub tags: Option<HashMap<String, Tag>>,
+}
+
+impl Repo {
+    /// What changed from `self` to `newer`.
+    #[must_use]
+    pub fn diff(&self, newer: &Self) -> RepoDiff {
+        let mut diff = RepoDiff::default();
+        for (name, branch) in &newer.branches {
+            match self.branches.get(name) {
+                None => {
+                    diff.branches_added.insert(name.clone());
+                }
+                Some(old) if old.leaf != branch.leaf => {
+                    diff.branches_moved.insert(name.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        for name in self.branches.keys() {
+            if !newer.branches.contains_key(name) {
+                diff.branches_removed.insert(name.clone());
+            }
+        }
+        for (name, addr) in &newer.remotes {
+            match self.remotes.get(name) {
+                None => {
+                    diff.remotes_added.insert(name.clone());
+                }
+                Some(old) if old != addr => {
+                    diff.remotes_changed.insert(name.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        for name in self.remotes.keys() {
+            if !newer.remotes.contains_key(name) {
+                diff.remotes_removed.insert(name.clone());
+            }
+        }
+        // Else every tag would look added once they start being read.
+        let (Some(old_tags), Some(new_tags)) = (&self.tags, &newer.tags)
+        else {
+            return diff;
+        };
+        for (name, tag) in new_tags {
+            match old_tags.get(name) {
+                None => {
+                    diff.tags_added.insert(name.clone());
+                }
+                Some(old) if old != tag => {
+                    diff.tags_moved.insert(name.clone());
+                }
+                Some(_) => {}
+            }
+        }
+        for name in old_tags.keys() {
+            if !new_tags.contains_key(name) {
+                diff.tags_removed.insert(name.clone());
+            }
+        }
+        diff
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RepoDiff {
+    pub branches_added: BTreeSet<String>,
+    pub branches_removed: BTreeSet<String>,
+    /// Branches whose leaf changed.
+    pub branches_moved: BTreeSet<String>,
+    pub remotes_added: BTreeSet<String>,
+    pub remotes_removed: BTreeSet<String>,
+    /// Remotes whose address changed.
+    pub remotes_changed: BTreeSet<String>,
+    pub tags_added: BTreeSet<String>,
+    pub tags_removed: BTreeSet<String>,
+    /// Tags re-pointed, re-annotated, or turned from lightweight to
+    /// annotated or back.
+    pub tags_moved: BTreeSet<String>,
+}
+
+impl RepoDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.branches_added.is_empty()
+            && self.branches_removed.is_empty()
+            && self.branches_moved.is_empty()
+            && self.remotes_added.is_empty()
+            && self.remotes_removed.is_empty()
+            && self.remotes_changed.is_empty()
+            && self.tags_added.is_empty()
+            && self.tags_removed.is_empty()
+            && self.tags_moved.is_empty()
+    }
+}
+
+impl fmt::Display for RepoDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups = [
+            ('+', "branch", &self.branches_added),
+            ('-', "branch", &self.branches_removed),
+            ('~', "branch", &self.branches_moved),
+            ('+', "remote", &self.remotes_added),
+            ('-', "remote", &self.remotes_removed),
+            ('~', "remote", &self.remotes_changed),
+            ('+', "tag", &self.tags_added),
+            ('-', "tag", &self.tags_removed),
+            ('~', "tag", &self.tags_moved),
+        ];
+        for (sign, kind, names) in groups {
+            for name in names {
+                writeln!(f, "{sign} {kind} {name}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// SQL condition on views, for those which could not be read if
+/// `failed_only`, else for all. Such a repo is stored as JSON `null`.
+fn failed_filter(failed_only: bool) -> &'static str {
+    if failed_only {
+        "(repo IS NULL OR repo = 'null')"
+    } else {
+        "1"
+    }
+}
+
+/// Whether the error is SQLite's SQLITE_BUSY or SQLITE_LOCKED, in any of
+/// their extended forms, which pass once the other writer is done.
+fn is_locked(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<sqlx::Error>()
+        .and_then(sqlx::Error::as_database_error)
+        .and_then(|error| error.code())
+        .and_then(|code| code.parse::<i32>().ok())
+        .is_some_and(|code| matches!(code & 0xff, 5 | 6))
 }
 
 pub struct Storage {
@@ -40,38 +465,544 @@ pub struct Storage {
 }
 
 impl Storage {
+    /// Refuses databases other than ours, so a mistaken path doesn't get
+    /// migrated into one. See [`Self::connect_force`].
     pub async fn connect<P: AsRef<Path>>(file: P) -> anyhow::Result<Self> {
-        let file = file.as_ref();
+        Self::connect_checked(file.as_ref(), true).await
+    }
+
+    /// Connects and migrates even a database that doesn't look like ours.
+    pub async fn connect_force<P: AsRef<Path>>(
+        file: P,
+    ) -> anyhow::Result<Self> {
+        Self::connect_checked(file.as_ref(), false).await
+    }
+
+    async fn connect_checked(
+        file: &Path,
+        check: bool,
+    ) -> anyhow::Result<Self> {
         if let Some(parent) = file.parent() {
             fs::create_dir_all(&parent).await?;
         }
         let url = format!("sqlite://{}?mode=rwc", file.to_string_lossy());
+        let options =
+            SqliteConnectOptions::from_str(&url)?.busy_timeout(BUSY_TIMEOUT);
         let pool = sqlx::sqlite::SqlitePoolOptions::new()
             .max_connections(5)
-            .connect(&url)
+            .connect_with(options)
             .await?;
         let selph = Self { pool };
-        for migration in MIGRATIONS {
-            selph.pool.execute(migration).await?;
+        if check {
+            selph.check_ours(file).await?;
         }
+        selph.migrate().await?;
         Ok(selph)
     }
 
+    /// Ours have a views table from the first migration on, so any other
+    /// tables without it mean some other program's database.
+    async fn check_ours(&self, file: &Path) -> anyhow::Result<()> {
+        let tables: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM sqlite_master WHERE type = 'table'",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        if !tables.is_empty() && !tables.iter().any(|(name,)| name == "views")
+        {
+            anyhow::bail!(
+                "Not a git-tracker database, so refusing to modify it: \
+                file={file:?}. Use --force to use it anyway."
+            );
+        }
+        Ok(())
+    }
+
+    /// For commands which only read, so a mistyped path fails rather than
+    /// creating an empty database. Migrations are left unapplied, so
+    /// tables and columns added by them may be missing, in which case
+    /// queries read them as empty or at their defaults.
+    pub async fn open_readonly<P: AsRef<Path>>(
+        file: P,
+    ) -> anyhow::Result<Self> {
+        let file = file.as_ref();
+        if !file.try_exists()? {
+            anyhow::bail!("Database file not found: {file:?}");
+        }
+        let url = format!("sqlite://{}?mode=ro", file.to_string_lossy());
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect(&url)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    async fn has_table(&self, table: &str) -> anyhow::Result<bool> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sqlite_master \
+            WHERE type = 'table' AND name = ?",
+        )
+        .bind(table)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+
+    async fn has_column(
+        &self,
+        table: &str,
+        column: &str,
+    ) -> anyhow::Result<bool> {
+        let (count,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count > 0)
+    }
+
+    /// Applies the migrations not yet recorded in `user_version`.
+    async fn migrate(&self) -> anyhow::Result<()> {
+        let (version,): (i64,) = sqlx::query_as("PRAGMA user_version")
+            .fetch_one(&self.pool)
+            .await?;
+        let version = usize::try_from(version)?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let mut tx = self.pool.begin().await?;
+            (&mut *tx).execute(*migration).await?;
+            (&mut *tx)
+                .execute(format!("PRAGMA user_version = {}", i + 1).as_str())
+                .await?;
+            tx.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the file without the space left by replaced and deleted
+    /// rows, and lets SQLite refresh its query planner statistics.
+    pub async fn vacuum(&self) -> anyhow::Result<()> {
+        self.pool.execute("VACUUM").await?;
+        self.pool.execute("PRAGMA optimize").await?;
+        Ok(())
+    }
+
+    /// Stores the views in one transaction, trying again while the
+    /// database is locked by another writer.
+    #[tracing::instrument(skip_all, fields(n = views.len()))]
     pub async fn store_views(&self, views: &[View]) -> anyhow::Result<()> {
+        let mut attempt = 1;
+        loop {
+            match self.store_views_once(views).await {
+                Err(error)
+                    if attempt < STORE_ATTEMPTS && is_locked(&error) =>
+                {
+                    tracing::warn!(attempt, ?error, "Database locked.");
+                    tokio::time::sleep(Duration::from_secs(1 << attempt))
+                        .await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn store_views_once(&self, views: &[View]) -> anyhow::Result<()> {
         let mut tx = self.pool.begin().await?;
         for view in views {
-            let View { host, link, repo } = view;
+            let View {
+                host,
+                link,
+                repo,
+                scanned_at,
+                probe_ms,
+                failure,
+                reachable,
+                error,
+            } = view;
             let link = serde_json::to_string(link)?;
             let repo = serde_json::to_string(repo)?;
+            let probe_ms = probe_ms.map(i64::try_from).transpose()?;
+            let failure =
+                failure.map(|f| serde_json::to_string(&f)).transpose()?;
+            let _id = sqlx::query(
+                "INSERT OR REPLACE INTO views (host, link, repo, scanned_at, probe_ms, failure, reachable, error) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            )
+                .bind(host)
+                .bind(&link)
+                .bind(&repo)
+                .bind(scanned_at)
+                .bind(probe_ms)
+                .bind(&failure)
+                .bind(reachable)
+                .bind(error)
+                .execute(&mut *tx).await?.last_insert_rowid();
             let _id = sqlx::query(
-                "INSERT OR REPLACE INTO views (host, link, repo) VALUES (?, ?, ?)"
+                "INSERT INTO history (host, link, repo, scanned_at, probe_ms, failure, reachable, error) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
             )
                 .bind(host)
-                .bind(link)
-                .bind(repo)
+                .bind(&link)
+                .bind(&repo)
+                .bind(scanned_at)
+                .bind(probe_ms)
+                .bind(&failure)
+                .bind(reachable)
+                .bind(error)
                 .execute(&mut *tx).await?.last_insert_rowid();
         }
         tx.commit().await?;
         Ok(())
     }
+
+    /// Decodes every row of every table of views, collecting the ones
+    /// which fail, e.g. because the stored JSON no longer matches the
+    /// types. Tables not yet created by migrations are left out.
+    pub async fn check(&self) -> anyhow::Result<Vec<TableCheck>> {
+        use sqlx::FromRow;
+
+        let mut checks = Vec::new();
+        for table in ["views", "history"] {
+            if !self.has_table(table).await? {
+                tracing::warn!(table, "Table missing. Not migrated yet?");
+                continue;
+            }
+            let mut check = TableCheck {
+                table,
+                rows: 0,
+                unreadable: Vec::new(),
+            };
+            let query = format!("SELECT * FROM {table} ORDER BY id");
+            let mut rows = sqlx::query(&query).fetch(&self.pool);
+            while let Some(row) = rows.next().await {
+                let row = row?;
+                check.rows += 1;
+                if let Err(error) = View::from_row(&row) {
+                    let id: i64 = row.try_get("id")?;
+                    check.unreadable.push((id, error.to_string()));
+                }
+            }
+            checks.push(check);
+        }
+        Ok(checks)
+    }
+
+    /// Latest stored view of every link.
+    pub async fn views(&self) -> anyhow::Result<Vec<View>> {
+        let views = sqlx::query_as::<_, View>("SELECT * FROM views")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(views)
+    }
+
+    /// Number of links with a stored view, or only of those which could
+    /// not be read.
+    pub async fn count_views(
+        &self,
+        failed_only: bool,
+    ) -> anyhow::Result<u64> {
+        let query = format!(
+            "SELECT COUNT(*) FROM views WHERE {}",
+            failed_filter(failed_only)
+        );
+        let (count,): (i64,) =
+            sqlx::query_as(&query).fetch_one(&self.pool).await?;
+        Ok(u64::try_from(count)?)
+    }
+
+    /// Latest stored view of every link, or only of those which could not
+    /// be read, sorted, skipping the first `offset` and at most `limit` of
+    /// them. Ties are broken by insertion order, so pages are stable while
+    /// nothing is stored.
+    pub async fn views_sorted(
+        &self,
+        order: ViewOrder,
+        reverse: bool,
+        failed_only: bool,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> anyhow::Result<Vec<View>> {
+        let direction = if reverse { "DESC" } else { "ASC" };
+        // Without the column, all were scanned at its default, so only the
+        // tie-break is left.
+        let order = if order == ViewOrder::Scanned
+            && !self.has_column("views", "scanned_at").await?
+        {
+            String::new()
+        } else {
+            format!("{} {direction}, ", order.sql())
+        };
+        let query = format!(
+            "SELECT * FROM views WHERE {} ORDER BY {order}id LIMIT ? OFFSET ?",
+            failed_filter(failed_only)
+        );
+        // SQLite takes a negative limit as none.
+        let limit = limit.map_or(Ok(-1), i64::try_from)?;
+        let views = sqlx::query_as::<_, View>(&query)
+            .bind(limit)
+            .bind(i64::try_from(offset)?)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(views)
+    }
+
+    /// Local dirs of the host which have a stored view.
+    pub async fn local_dirs(
+        &self,
+        host: &str,
+    ) -> anyhow::Result<HashSet<PathBuf>> {
+        let links: Vec<(String,)> =
+            sqlx::query_as("SELECT link FROM views WHERE host = ?")
+                .bind(host)
+                .fetch_all(&self.pool)
+                .await?;
+        let mut dirs = HashSet::new();
+        for (link,) in links {
+            if let Link::Fs { dir } = serde_json::from_str(&link)? {
+                dirs.insert(dir);
+            }
+        }
+        Ok(dirs)
+    }
+
+    /// Every host with the number of links it has views of.
+    pub async fn hosts(&self) -> anyhow::Result<Vec<(String, i64)>> {
+        let hosts = sqlx::query_as(
+            "SELECT host, COUNT(*) FROM views GROUP BY host ORDER BY host",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(hosts)
+    }
+
+    /// Latest stored view of every link, streamed host by host, in
+    /// insertion order.
+    pub fn views_stream(&self) -> BoxStream<'_, anyhow::Result<View>> {
+        sqlx::query_as::<_, View>("SELECT * FROM views ORDER BY host, id")
+            .fetch(&self.pool)
+            .map(|view| Ok(view?))
+            .boxed()
+    }
+
+    /// Every stored view of every link, streamed host by host, oldest
+    /// first. None if the history table was not yet created by migrations.
+    pub fn history_stream(&self) -> BoxStream<'_, anyhow::Result<View>> {
+        stream::once(self.has_table("history"))
+            .flat_map(move |has_history| match has_history {
+                Ok(true) => sqlx::query_as::<_, View>(
+                    "SELECT * FROM history ORDER BY host, scanned_at, id",
+                )
+                .fetch(&self.pool)
+                .map(|view| Ok(view?))
+                .boxed(),
+                Ok(false) => stream::empty().boxed(),
+                Err(error) => stream::once(async { Err(error) }).boxed(),
+            })
+            .boxed()
+    }
+
+    /// All stored views of the given link, oldest first. None if the
+    /// history table was not yet created by migrations.
+    pub async fn history(
+        &self,
+        host: &str,
+        link: &Link,
+    ) -> anyhow::Result<Vec<View>> {
+        if !self.has_table("history").await? {
+            return Ok(Vec::new());
+        }
+        let views = sqlx::query_as::<_, View>(
+            "SELECT * FROM history WHERE host = ? AND link = ? ORDER BY scanned_at, id"
+        )
+            .bind(host)
+            .bind(serde_json::to_string(link)?)
+            .fetch_all(&self.pool).await?;
+        Ok(views)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// View of a readable repo, with the given remotes and nothing else.
+    pub(crate) fn view(
+        host: &str,
+        link: &str,
+        remotes: &[(&str, &str)],
+    ) -> View {
+        let repo = Repo {
+            description: None,
+            remotes: remotes
+                .iter()
+                .map(|(name, url)| ((*name).to_string(), (*url).to_string()))
+                .collect(),
+            remote_urls: HashMap::new(),
+            branches: HashMap::new(),
+            roots: HashSet::new(),
+            identity_only: false,
+            head_detached: None,
+            last_fetched: HashMap::new(),
+            alternates: Vec::new(),
+            hooks: Vec::new(),
+            skipped_too_large: false,
+            latest_commit_time: None,
+            shallow: false,
+            tags: None,
+        };
+        View {
+            host: host.to_string(),
+            link: link.parse().unwrap(),
+            repo: Some(repo),
+            scanned_at: 1,
+            probe_ms: None,
+            failure: None,
+            reachable: None,
+            error: None,
+        }
+    }
+
+    /// A database as the first release made it, before any of the later
+    /// migrations.
+    async fn unmigrated(file: &Path) {
+        let url = format!("sqlite://{}?mode=rwc", file.to_string_lossy());
+        let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+        pool.execute(MIGRATIONS[0]).await.unwrap();
+        sqlx::query("INSERT INTO views (host, link, repo) VALUES (?, ?, ?)")
+            .bind("host")
+            .bind(r#"{"Fs":{"dir":"/src/a"}}"#)
+            .bind("null")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+    }
+
+    #[tokio::test]
+    async fn readonly_reads_unmigrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("old.db");
+        unmigrated(&file).await;
+        let storage = Storage::open_readonly(&file).await.unwrap();
+
+        let views = storage.views().await.unwrap();
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].scanned_at, 0);
+        assert_eq!(views[0].error, None);
+        let sorted = storage
+            .views_sorted(ViewOrder::Scanned, true, false, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(sorted, views);
+        assert_eq!(storage.count_views(false).await.unwrap(), 1);
+        // Unreadable, as stored then.
+        assert_eq!(storage.count_views(true).await.unwrap(), 1);
+        assert_eq!(storage.hosts().await.unwrap(), [("host".into(), 1)]);
+        let link = views[0].link.clone();
+        assert!(storage.history("host", &link).await.unwrap().is_empty());
+        assert_eq!(storage.history_stream().count().await, 0);
+
+        let checks = storage.check().await.unwrap();
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].table, "views");
+        assert_eq!(checks[0].rows, 1);
+        assert!(checks[0].unreadable.is_empty());
+    }
+
+    #[tokio::test]
+    async fn readonly_refuses_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("typo.db");
+        assert!(Storage::open_readonly(&file).await.is_err());
+        assert!(!file.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_writers_lose_nothing() {
+        const WRITERS: usize = 8;
+        const BATCHES: usize = 10;
+        const BATCH: usize = 50;
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("hammered.db");
+        Storage::connect(&file).await.unwrap();
+        let writers = (0..WRITERS).map(|w| {
+            let file = file.clone();
+            tokio::spawn(async move {
+                // Each with its own pool, as separate processes would be.
+                let storage = Storage::connect(&file).await?;
+                for b in 0..BATCHES {
+                    let views: Vec<View> = (0..BATCH)
+                        .map(|v| view("host", &format!("/{w}/{b}/{v}"), &[]))
+                        .collect();
+                    storage.store_views(&views).await?;
+                }
+                anyhow::Ok(())
+            })
+        });
+        for result in futures::future::join_all(writers).await {
+            result.unwrap().unwrap();
+        }
+        let storage = Storage::open_readonly(&file).await.unwrap();
+        let stored = WRITERS * BATCHES * BATCH;
+        assert_eq!(storage.count_views(false).await.unwrap(), stored as u64);
+        assert_eq!(storage.history_stream().count().await, stored);
+    }
+
+    #[tokio::test]
+    async fn failed_only_are_unread() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = Storage::connect(&dir.path().join("db")).await.unwrap();
+        let mut failed = view("host", "/src/b", &[]);
+        failed.repo = None;
+        failed.error = Some("fatal: not a git repository".to_string());
+        storage
+            .store_views(&[view("host", "/src/a", &[]), failed.clone()])
+            .await
+            .unwrap();
+        assert_eq!(storage.count_views(false).await.unwrap(), 2);
+        assert_eq!(storage.count_views(true).await.unwrap(), 1);
+        let views = storage
+            .views_sorted(ViewOrder::Path, false, true, None, 0)
+            .await
+            .unwrap();
+        assert_eq!(views, [failed]);
+    }
+
+    #[tokio::test]
+    async fn connect_refuses_foreign_db() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("other.db");
+        let url = format!("sqlite://{}?mode=rwc", file.to_string_lossy());
+        let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+        pool.execute("CREATE TABLE invoices (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let error = Storage::connect(&file).await.err().unwrap();
+        assert!(error.to_string().contains("Not a git-tracker database"));
+        let storage = Storage::open_readonly(&file).await.unwrap();
+        assert!(!storage.has_table("views").await.unwrap());
+
+        let storage = Storage::connect_force(&file).await.unwrap();
+        storage
+            .store_views(&[view("h", "/src/a", &[])])
+            .await
+            .unwrap();
+        assert_eq!(storage.count_views(false).await.unwrap(), 1);
+        // Now ours, so no force needed.
+        Storage::connect(&file).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connect_takes_new_empty_and_ours() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("empty.db");
+        std::fs::File::create(&file).unwrap();
+        Storage::connect(&file).await.unwrap();
+        let file = dir.path().join("old.db");
+        unmigrated(&file).await;
+        Storage::connect(&file).await.unwrap();
+    }
 }
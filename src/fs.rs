@@ -1,31 +1,269 @@
 use std::{
-    collections::HashSet,
-    os::unix::ffi::OsStrExt,
+    collections::{BTreeSet, HashSet},
+    ffi::OsStr,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
 };
 
+/// Whether the last component of the path is ".git", in any ASCII case
+/// if case is ignored, as git would take ".GIT" for ".git" on a
+/// case-insensitive file system.
+#[must_use]
+pub fn is_dot_git(path: &Path, ignore_case: bool) -> bool {
+    path.file_name().is_some_and(|name| {
+        if ignore_case {
+            name.eq_ignore_ascii_case(".git")
+        } else {
+            name == ".git"
+        }
+    })
+}
+
+/// Dirs under root, not descending into them, whose names match any of
+/// the target patterns, as in [`name_matches`], with or without ignoring
+/// case.
+///
+/// With `prune`, a dir with a `.git` dir in it is taken for a work tree,
+/// and none of the rest of it is walked, so nested repos, e.g. submodules,
+/// are missed. Except for the work tree at root itself, e.g. a home dir
+/// kept in git.
+///
+/// With `print_ignored`, what's skipped for being ignored or pruned is
+/// logged at info level, to check the rules do what's intended.
 #[tracing::instrument]
 pub fn find_dirs(
     root: &Path,
-    target_name: &str,
+    target_names: &[String],
+    ignore_case: bool,
     follow: bool,
+    prune: bool,
     ignore: &HashSet<PathBuf>,
-) -> impl Iterator<Item = PathBuf> {
+    print_ignored: bool,
+) -> Dirs {
     let root = root.to_path_buf();
     Dirs {
         ignore: ignore.to_owned(),
         follow,
-        target_name: target_name.to_string(),
-        frontier: vec![root],
+        prune,
+        print_ignored,
+        target_names: target_names.to_vec(),
+        ignore_case,
+        frontier: vec![root.clone()],
+        root,
+        deadline: None,
     }
 }
 
+/// Like [`find_dirs`], without pruning, but with `find` run on the
+/// target, which may be over SSH, and whose `find` takes the same `*` and
+/// `?` patterns, and ignores case with `-iname`.
+#[tracing::instrument]
+pub async fn find_dirs_over_ssh(
+    target: &crate::os::Target,
+    roots: &[PathBuf],
+    target_names: &[String],
+    ignore_case: bool,
+    follow: bool,
+    ignore: &HashSet<PathBuf>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut args: Vec<&OsStr> = Vec::new();
+    if follow {
+        args.push(OsStr::new("-L"));
+    }
+    args.extend(roots.iter().map(|root| root.as_os_str()));
+    if !ignore.is_empty() {
+        args.push(OsStr::new("("));
+        for (i, path) in ignore.iter().enumerate() {
+            if i > 0 {
+                args.push(OsStr::new("-o"));
+            }
+            args.extend([OsStr::new("-path"), path.as_os_str()]);
+        }
+        args.extend([")", "-prune", "-o"].map(OsStr::new));
+    }
+    args.extend(["-type", "d", "("].map(OsStr::new));
+    let name_test = if ignore_case { "-iname" } else { "-name" };
+    for (i, name) in target_names.iter().enumerate() {
+        if i > 0 {
+            args.push(OsStr::new("-o"));
+        }
+        args.extend([OsStr::new(name_test), OsStr::new(name)]);
+    }
+    args.extend([")", "-print0", "-prune"].map(OsStr::new));
+    let out = target.cmd("find", &args).await?;
+    let dirs = out
+        .split(|&b| b == 0)
+        .filter(|path| !path.is_empty())
+        .map(crate::git::path_from_bytes)
+        .collect();
+    Ok(dirs)
+}
+
+/// Git dirs in the tar archive, e.g. "proj/.git", as found in its listing,
+/// without extracting it. Only dirs named like the target patterns, as in
+/// [`name_matches`], count, so bare repos are missed.
+pub async fn archive_git_dirs(
+    archive: &Path,
+    target_names: &[String],
+    ignore_case: bool,
+) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let listing =
+        crate::os::cmd("tar", &[OsStr::new("-tf"), archive.as_os_str()])
+            .await?;
+    let mut dirs = BTreeSet::new();
+    for entry in listing.split(|&b| b == b'\n') {
+        let entry = crate::git::path_from_bytes(entry);
+        let mut prefix = PathBuf::new();
+        let mut components = entry.components();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            if name_matches(component.as_os_str(), target_names, ignore_case)
+            {
+                // Else a file, e.g. the .git file of a linked work tree.
+                if components.next().is_some() {
+                    dirs.insert(prefix);
+                }
+                break;
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+/// Extracts just the given dirs of the tar archive, with all beneath them,
+/// under the destination dir.
+pub async fn extract_from_archive(
+    archive: &Path,
+    dirs: &BTreeSet<PathBuf>,
+    dst: &Path,
+) -> anyhow::Result<()> {
+    // Without any, tar would extract everything.
+    if dirs.is_empty() {
+        return Ok(());
+    }
+    let mut args = vec![
+        OsStr::new("-xf"),
+        archive.as_os_str(),
+        OsStr::new("-C"),
+        dst.as_os_str(),
+        OsStr::new("--"),
+    ];
+    args.extend(dirs.iter().map(|dir| dir.as_os_str()));
+    crate::os::cmd("tar", &args).await?;
+    Ok(())
+}
+
+/// Whether the name matches any of the patterns, in which `*` stands for
+/// any run of bytes and `?` for any single byte. Compares the raw encoded
+/// bytes, so non-UTF-8 names work, and not just on Unix. Ignores ASCII
+/// case if told to.
+#[must_use]
+pub fn name_matches(
+    name: &OsStr,
+    patterns: &[String],
+    ignore_case: bool,
+) -> bool {
+    patterns.iter().any(|pattern| {
+        glob(pattern.as_bytes(), name.as_encoded_bytes(), ignore_case)
+    })
+}
+
+fn glob(pattern: &[u8], name: &[u8], ignore_case: bool) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Where to resume after the last `*`, should the rest fail to match.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c)
+                if c == b'?'
+                    || c == name[n]
+                    || (ignore_case && c.eq_ignore_ascii_case(&name[n])) =>
+            {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Git dirs of the submodules of the repo with the given git dir, as git
+/// keeps them, under its `modules` dir, and of their own submodules in
+/// turn. Repos without submodules have no such dir, which isn't an error.
+pub fn submodule_git_dirs(git_dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut frontier = vec![git_dir.join("modules")];
+    while let Some(dir) = frontier.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                continue;
+            }
+            let path = entry.path();
+            if path.join("HEAD").is_file() && path.join("objects").is_dir() {
+                frontier.push(path.join("modules"));
+                found.push(path);
+            } else {
+                // Part of a submodule name with slashes, e.g. "libs/foo".
+                frontier.push(path);
+            }
+        }
+    }
+    found
+}
+
+/// Paths of the dir's entries, e.g. to walk its subtrees separately.
+pub fn children(dir: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(dir) {
+        Err(error) => {
+            tracing::error!(?dir, ?error, "Failed to read directory");
+            Vec::new()
+        }
+        Ok(entries) => entries
+            .filter_map(|entry_result| match entry_result {
+                Ok(entry) => Some(entry.path()),
+                Err(error) => {
+                    tracing::error!(
+                        from = ?dir, ?error,
+                        "Failed to read an entry",
+                    );
+                    None
+                }
+            })
+            .collect(),
+    }
+}
+
+/// A walk, as started by [`find_dirs`].
 #[derive(Debug)]
-struct Dirs {
-    target_name: String,
+pub struct Dirs {
+    target_names: Vec<String>,
+    ignore_case: bool,
     follow: bool,
+    prune: bool,
+    print_ignored: bool,
     ignore: HashSet<PathBuf>,
     frontier: Vec<PathBuf>,
+    root: PathBuf,
+    deadline: Option<(Instant, Arc<AtomicBool>)>,
 }
 
 impl Iterator for Dirs {
@@ -36,8 +274,23 @@ impl Iterator for Dirs {
         // use tokio::fs;
         use std::fs;
 
+        let _span = tracing::debug_span!("walk").entered();
         while let Some(path) = self.frontier.pop() {
+            if let Some((deadline, passed)) = &self.deadline {
+                if Instant::now() >= *deadline {
+                    tracing::info!(
+                        root = ?self.root,
+                        "Deadline passed. Walking no further."
+                    );
+                    passed.store(true, Ordering::Relaxed);
+                    self.frontier.clear();
+                    return None;
+                }
+            }
             if self.ignore.contains(&path) {
+                if self.print_ignored {
+                    tracing::info!(?path, "Ignored.");
+                }
                 continue;
             }
             if !&path.try_exists().is_ok_and(|exists| exists) {
@@ -63,7 +316,11 @@ impl Iterator for Dirs {
                 }
                 Ok(meta) if meta.is_dir() => {
                     if path.file_name().is_some_and(|name| {
-                        name.as_bytes() == self.target_name.as_bytes()
+                        name_matches(
+                            name,
+                            &self.target_names,
+                            self.ignore_case,
+                        )
                     }) {
                         return Some(path);
                     }
@@ -76,11 +333,10 @@ impl Iterator for Dirs {
                             );
                         }
                         Ok(entries) => {
+                            let mut children = Vec::new();
                             for entry_result in entries {
                                 match entry_result {
-                                    Ok(entry) => {
-                                        self.frontier.push(entry.path());
-                                    }
+                                    Ok(entry) => children.push(entry),
                                     Err(error) => {
                                         tracing::error!(
                                             from = ?path, ?error,
@@ -89,6 +345,26 @@ impl Iterator for Dirs {
                                     }
                                 }
                             }
+                            let git_dir = children
+                                .iter()
+                                .filter(|_| self.prune && path != self.root)
+                                .map(fs::DirEntry::path)
+                                .find(|child| self.is_pruning_git_dir(child));
+                            if let Some(git_dir) = git_dir {
+                                // Only it, before any of the rest of the
+                                // work tree is walked.
+                                if self.print_ignored {
+                                    tracing::info!(
+                                        work_tree = ?path,
+                                        "Pruned rest of work tree."
+                                    );
+                                }
+                                self.frontier.push(git_dir);
+                            } else {
+                                self.frontier.extend(
+                                    children.iter().map(fs::DirEntry::path),
+                                );
+                            }
                         }
                     }
                 }
@@ -104,3 +380,184 @@ impl Iterator for Dirs {
         None
     }
 }
+
+impl Dirs {
+    /// Ends the walk at the deadline, if any, setting the flag, rather than
+    /// only once it next finds a dir, which may take a while.
+    #[must_use]
+    pub fn until(
+        mut self,
+        deadline: Option<Instant>,
+        passed: Arc<AtomicBool>,
+    ) -> Self {
+        self.deadline = deadline.map(|deadline| (deadline, passed));
+        self
+    }
+
+    /// Whether the path is a `.git` dir to be found, so the rest of the
+    /// work tree it's in can be pruned.
+    fn is_pruning_git_dir(&self, path: &Path) -> bool {
+        is_dot_git(path, self.ignore_case)
+            && path.file_name().is_some_and(|name| {
+                name_matches(name, &self.target_names, self.ignore_case)
+            })
+            && !self.ignore.contains(path)
+            && std::fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mkdirs(root: &Path, dirs: &[&str]) {
+        for dir in dirs {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+    }
+
+    fn found(root: &Path, prune: bool) -> BTreeSet<PathBuf> {
+        find_dirs(
+            root,
+            &[".git".to_string()],
+            false,
+            false,
+            prune,
+            &HashSet::new(),
+            false,
+        )
+        .map(|dir| dir.strip_prefix(root).unwrap().to_path_buf())
+        .collect()
+    }
+
+    #[test]
+    fn prune_skips_rest_of_work_tree() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        mkdirs(
+            root,
+            &[
+                ".git",
+                "home/proj/.git",
+                "home/proj/src/a/b/c",
+                "home/proj/vendor/lib/.git",
+                "home/other/.git",
+                "home/plain/deep/nested/.git",
+            ],
+        );
+        // Some would come before its .git in any read_dir order, so would
+        // be walked, were pruning left to the order.
+        for i in 0..20 {
+            mkdirs(root, &[&format!("home/proj/sub{i}/.git")]);
+        }
+        let paths = |paths: &[&str]| -> BTreeSet<PathBuf> {
+            paths.iter().map(PathBuf::from).collect()
+        };
+        assert_eq!(
+            found(root, true),
+            paths(&[
+                ".git",
+                "home/proj/.git",
+                "home/other/.git",
+                "home/plain/deep/nested/.git",
+            ])
+        );
+        let unpruned = found(root, false);
+        assert_eq!(unpruned.len(), 25);
+        assert!(unpruned.contains(Path::new("home/proj/vendor/lib/.git")));
+        assert!(unpruned.contains(Path::new("home/proj/sub0/.git")));
+    }
+
+    #[test]
+    fn glob_matches_case_as_set() {
+        for (pattern, name, exact, ignoring_case) in [
+            (".git", ".git", true, true),
+            (".git", ".GIT", false, true),
+            ("*.git", "Repo.GIT", false, true),
+            ("*.git", "repo.git", true, true),
+            ("?git", ".Git", false, true),
+            ("*", "", true, true),
+            (".git", ".gitx", false, false),
+            ("a*b*c", "AxxBxxC", false, true),
+            ("a*b*c", "axxbxxd", false, false),
+        ] {
+            let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+            assert_eq!(glob(pattern, name, false), exact, "{pattern:?}");
+            assert_eq!(glob(pattern, name, true), ignoring_case, "{name:?}");
+        }
+    }
+
+    #[test]
+    fn name_matches_bytes_in_case_as_given() {
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let name = OsStr::from_bytes(b"\xff.git");
+            assert!(name_matches(name, &["*.git".to_string()], false));
+            assert!(!name_matches(name, &["?.gi".to_string()], false));
+        }
+        for ignore_case in [false, true] {
+            let git = [".git".to_string()];
+            assert!(name_matches(OsStr::new(".git"), &git, ignore_case));
+            assert_eq!(
+                name_matches(OsStr::new(".GIT"), &git, ignore_case),
+                ignore_case
+            );
+            assert_eq!(
+                is_dot_git(Path::new("proj/.Git"), ignore_case),
+                ignore_case
+            );
+            assert!(is_dot_git(Path::new("proj/.git"), ignore_case));
+        }
+    }
+
+    #[test]
+    fn walk_ignores_case_per_call() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        mkdirs(root, &["a/.GIT", "b/.git"]);
+        let walk = |ignore_case| -> BTreeSet<PathBuf> {
+            find_dirs(
+                root,
+                &[".git".to_string()],
+                ignore_case,
+                false,
+                false,
+                &HashSet::new(),
+                false,
+            )
+            .map(|dir| dir.strip_prefix(root).unwrap().to_path_buf())
+            .collect()
+        };
+        assert_eq!(walk(false).len(), 1);
+        assert_eq!(walk(true).len(), 2);
+    }
+
+    #[test]
+    fn walk_stops_at_deadline() {
+        let root = tempfile::tempdir().unwrap();
+        let root = root.path();
+        mkdirs(root, &["a/.git"]);
+        let walk = || {
+            find_dirs(
+                root,
+                &[".git".to_string()],
+                false,
+                false,
+                false,
+                &HashSet::new(),
+                false,
+            )
+        };
+        let passed = Arc::new(AtomicBool::new(false));
+        let later = Instant::now() + std::time::Duration::from_secs(60);
+        assert_eq!(walk().until(Some(later), passed.clone()).count(), 1);
+        assert!(!passed.load(Ordering::Relaxed));
+        assert_eq!(
+            walk().until(Some(Instant::now()), passed.clone()).count(),
+            0
+        );
+        assert!(passed.load(Ordering::Relaxed));
+    }
+}
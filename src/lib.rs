@@ -2,25 +2,152 @@ pub mod cmd;
 pub mod data;
 pub mod fs;
 pub mod git;
+pub mod manifest;
 pub mod os;
+pub mod profile;
+pub mod redact;
 
+/// Environment variables to read log directives from, e.g.
+/// `git_tracker::git=trace`, in order of precedence.
+const LOG_ENV_VARS: [&str; 2] = ["GIT_TRACKER_LOG", "RUST_LOG"];
+
+/// Logs to stderr at `level`, refined per module by the directives in the
+/// first set of [`LOG_ENV_VARS`]. Logs nothing if neither is given.
+///
+/// Does nothing if a global subscriber is already set, whether by an
+/// earlier call or by the caller, so it isn't clobbered.
 pub fn tracing_init(level: Option<tracing::Level>) -> anyhow::Result<()> {
-    use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter, Layer};
-
-    if let Some(level) = level {
-        let layer_stderr = fmt::Layer::new()
-            .with_writer(std::io::stderr)
-            .with_ansi(true)
-            .with_file(false)
-            .with_line_number(true)
-            .with_thread_ids(true)
-            // .with_span_events(fmt::format::FmtSpan::CLOSE)
-            .with_filter(
-                EnvFilter::from_default_env().add_directive(level.into()),
-            );
-        tracing::subscriber::set_global_default(
-            tracing_subscriber::registry().with(layer_stderr),
-        )?;
+    use anyhow::Context;
+    use tracing_subscriber::{
+        filter::filter_fn, fmt, layer::SubscriberExt, EnvFilter, Layer,
+    };
+
+    if tracing::dispatcher::has_been_set() {
+        tracing::debug!("Tracing already initialized. Leaving it be.");
+        return Ok(());
+    }
+    let Some(directives) =
+        log_directives(level, |var| std::env::var(var).ok())
+    else {
+        return Ok(());
+    };
+    let filter = EnvFilter::try_new(&directives)
+        .with_context(|| format!("Invalid log directives: {directives:?}"))?;
+    let layer_stderr = fmt::Layer::new()
+        .with_writer(std::io::stderr)
+        .with_ansi(true)
+        .with_file(false)
+        .with_line_number(true)
+        .with_thread_ids(true)
+        // .with_span_events(fmt::format::FmtSpan::CLOSE)
+        .with_filter(filter);
+    // Only our own spans, regardless of log level.
+    let layer_profile = profile::Layer.with_filter(filter_fn(|meta| {
+        meta.is_span() && meta.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }));
+    let subscriber = tracing_subscriber::registry()
+        .with(layer_stderr)
+        .with(layer_profile);
+    // Lost a race with another initialization, which is just as good.
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        tracing::debug!("Tracing already initialized. Leaving it be.");
     }
     Ok(())
 }
+
+/// Directives for the level and the first of [`LOG_ENV_VARS`] which `env`
+/// finds set, if any. Later directives for the same target win, so the
+/// env overrides the level, even the default one.
+fn log_directives(
+    level: Option<tracing::Level>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    let env = LOG_ENV_VARS.iter().find_map(|var| env(var));
+    match (level, env) {
+        (None, None) => None,
+        (Some(level), None) => Some(level.to_string()),
+        (None, Some(env)) => Some(env),
+        (Some(level), Some(env)) => Some(format!("{level},{env}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use tracing::Level;
+    use tracing_subscriber::{layer::SubscriberExt, EnvFilter, Layer};
+
+    use super::*;
+
+    fn directives(
+        level: Option<Level>,
+        env: &[(&str, &str)],
+    ) -> Option<String> {
+        let env: HashMap<&str, &str> = env.iter().copied().collect();
+        log_directives(level, |var| env.get(var).map(ToString::to_string))
+    }
+
+    #[test]
+    fn log_env_precedence() {
+        assert_eq!(directives(None, &[]), None);
+        assert_eq!(
+            directives(Some(Level::WARN), &[]).as_deref(),
+            Some("WARN")
+        );
+        assert_eq!(
+            directives(None, &[("RUST_LOG", "debug")]).as_deref(),
+            Some("debug")
+        );
+        assert_eq!(
+            directives(
+                Some(Level::WARN),
+                &[
+                    ("RUST_LOG", "debug"),
+                    ("GIT_TRACKER_LOG", "git_tracker::git=trace")
+                ]
+            )
+            .as_deref(),
+            Some("WARN,git_tracker::git=trace")
+        );
+    }
+
+    /// Counts the events it lets through.
+    struct Count(Arc<AtomicUsize>);
+
+    impl<S: tracing::Subscriber> Layer<S> for Count {
+        fn on_event(
+            &self,
+            _: &tracing::Event<'_>,
+            _: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn env_filter_takes_effect() {
+        let directives = directives(
+            Some(Level::WARN),
+            &[("GIT_TRACKER_LOG", "git_tracker::git=trace")],
+        )
+        .unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let filter = EnvFilter::try_new(directives).unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(Count(count.clone()).with_filter(filter));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::trace!(target: "git_tracker::git", "Let through.");
+            tracing::trace!(target: "git_tracker::fs", "Filtered out.");
+            tracing::info!(target: "git_tracker::fs", "Filtered out.");
+            tracing::warn!(target: "git_tracker::fs", "Let through.");
+        });
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+    }
+}
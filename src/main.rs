@@ -3,29 +3,139 @@ use tracing::{info_span, Instrument};
 
 #[derive(Parser, Debug)]
 struct Cli {
-    /// Specify log level.
+    /// Specify log level. Overridden per module by directives in
+    /// GIT_TRACKER_LOG or else RUST_LOG, e.g. git_tracker::git=trace.
     #[clap(short, long = "log", default_value_t = tracing::Level::DEBUG)]
     log_level: tracing::Level,
 
+    /// When to color human output.
+    #[clap(long, value_enum, default_value_t = git_tracker::cmd::Color::Auto)]
+    color: git_tracker::cmd::Color,
+
     #[clap(subcommand)]
     command: Cmd,
 }
 
 #[derive(Debug, clap::Subcommand)]
 enum Cmd {
+    /// List local repos with no remotes, or none reachable, which are
+    /// at risk of being lost.
+    AtRisk(git_tracker::cmd::at_risk::Cmd),
+
+    /// Compare the repos stored in two databases.
+    Diff(git_tracker::cmd::diff::Cmd),
+
+    /// Check that git, the database file, SSH and temporary space are
+    /// ready for a scan.
+    Doctor(git_tracker::cmd::doctor::Cmd),
+
+    /// Group local repos, across hosts, which are copies of one project.
+    Duplicates(git_tracker::cmd::duplicates::Cmd),
+
+    /// Dump stored views as JSON.
+    Export(git_tracker::cmd::export::Cmd),
+
     /// Find all git repos under the given directories.
-    Find(git_tracker::cmd::find::Cmd),
+    ///
+    /// On Unix, sending it SIGUSR1, e.g. with `pkill -USR1 git-tracker`,
+    /// prints the progress so far to stderr: the phase, repos found,
+    /// remotes read and views stored.
+    Find(Box<git_tracker::cmd::find::Cmd>),
+
+    /// Show how a repo changed across stored scans.
+    History(git_tracker::cmd::history::Cmd),
+
+    /// List the hosts which scanned the stored repos.
+    Hosts(git_tracker::cmd::hosts::Cmd),
+
+    /// Load views written by `export` into a database.
+    Import(git_tracker::cmd::import::Cmd),
+
+    /// List the latest view of every stored repo, sorted.
+    List(git_tracker::cmd::list::Cmd),
+
+    /// List the remotes of stored local repos, with how many point at
+    /// each and whether it was readable.
+    Remotes(git_tracker::cmd::remotes::Cmd),
+
+    /// Probe again the remotes which timed out or failed last time.
+    Retry(git_tracker::cmd::retry::Cmd),
+
+    /// Serve the stored views over HTTP as JSON.
+    Serve(git_tracker::cmd::serve::Cmd),
+
+    /// Summarize the stored views.
+    Stats(git_tracker::cmd::stats::Cmd),
+
+    /// Shrink the database file to what its rows take up.
+    Vacuum(git_tracker::cmd::vacuum::Cmd),
+
+    /// Check that every stored row can still be read, failing if not.
+    VerifyDb(git_tracker::cmd::verify_db::Cmd),
+
+    /// Keep watching the given directories and store new repos as they
+    /// appear.
+    Watch(git_tracker::cmd::watch::Cmd),
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
     git_tracker::tracing_init(Some(cli.log_level))?;
+    cli.color.init();
     tracing::debug!(?cli, "Starting");
     match cli.command {
+        Cmd::AtRisk(cmd) => {
+            cmd.run().instrument(info_span!("at_risk")).await?;
+        }
+        Cmd::Diff(cmd) => {
+            cmd.run().instrument(info_span!("diff")).await?;
+        }
+        Cmd::Doctor(cmd) => {
+            cmd.run().instrument(info_span!("doctor")).await?;
+        }
+        Cmd::Duplicates(cmd) => {
+            cmd.run().instrument(info_span!("duplicates")).await?;
+        }
+        Cmd::Export(cmd) => {
+            cmd.run().instrument(info_span!("export")).await?;
+        }
         Cmd::Find(cmd) => {
             cmd.run().instrument(info_span!("find")).await?;
         }
+        Cmd::History(cmd) => {
+            cmd.run().instrument(info_span!("history")).await?;
+        }
+        Cmd::Hosts(cmd) => {
+            cmd.run().instrument(info_span!("hosts")).await?;
+        }
+        Cmd::Import(cmd) => {
+            cmd.run().instrument(info_span!("import")).await?;
+        }
+        Cmd::List(cmd) => {
+            cmd.run().instrument(info_span!("list")).await?;
+        }
+        Cmd::Remotes(cmd) => {
+            cmd.run().instrument(info_span!("remotes")).await?;
+        }
+        Cmd::Retry(cmd) => {
+            cmd.run().instrument(info_span!("retry")).await?;
+        }
+        Cmd::Serve(cmd) => {
+            cmd.run().instrument(info_span!("serve")).await?;
+        }
+        Cmd::Stats(cmd) => {
+            cmd.run().instrument(info_span!("stats")).await?;
+        }
+        Cmd::Vacuum(cmd) => {
+            cmd.run().instrument(info_span!("vacuum")).await?;
+        }
+        Cmd::VerifyDb(cmd) => {
+            cmd.run().instrument(info_span!("verify_db")).await?;
+        }
+        Cmd::Watch(cmd) => {
+            cmd.run().instrument(info_span!("watch")).await?;
+        }
     }
     Ok(())
 }
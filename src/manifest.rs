@@ -0,0 +1,75 @@
+//! Manifest of the local repos found by a scan, for backup tools to decide
+//! what to mirror. Unlike the database, its shape is stable: any change to
+//! it comes with a new [`SCHEMA_VERSION`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Link, View};
+
+/// Version of the shape of [`Manifest`], recorded in each one.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    pub schema_version: u32,
+    /// Host on which the repos were found.
+    pub host: String,
+    /// Sorted by path.
+    pub repos: Vec<Repo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Repo {
+    /// Git dir, as stored in the database. Non-UTF-8 bytes are replaced,
+    /// since JSON strings can't hold them.
+    pub path: String,
+    /// URLs by remote name.
+    pub remotes: BTreeMap<String, String>,
+    /// Root commits of all branches, or of all refs in identity-only
+    /// scans. They identify the repo, whatever its path or remotes.
+    pub roots: BTreeSet<String>,
+}
+
+impl Repo {
+    /// `None` for remotes and for local repos which could not be read.
+    #[must_use]
+    pub fn from_view(view: &View) -> Option<Self> {
+        let (Link::Fs { dir }, Some(repo)) = (&view.link, &view.repo) else {
+            return None;
+        };
+        let roots = repo
+            .roots
+            .iter()
+            .chain(repo.branches.values().flat_map(|b| b.roots.iter()))
+            .cloned()
+            .collect();
+        Some(Self {
+            path: dir.to_string_lossy().into_owned(),
+            remotes: repo.remotes.clone().into_iter().collect(),
+            roots,
+        })
+    }
+}
+
+impl Manifest {
+    pub fn new(host: &str, repos: impl IntoIterator<Item = Repo>) -> Self {
+        let mut repos: Vec<Repo> = repos.into_iter().collect();
+        repos.sort_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            schema_version: SCHEMA_VERSION,
+            host: host.to_string(),
+            repos,
+        }
+    }
+
+    pub async fn write(&self, file: &Path) -> anyhow::Result<()> {
+        crate::os::write_whole(file, serde_json::to_vec_pretty(self)?)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+//! Time spent in each of our tracing spans, totaled by span name.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TOTALS: OnceLock<Mutex<BTreeMap<&'static str, Total>>> =
+    OnceLock::new();
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Total {
+    /// Number of spans closed.
+    pub count: u64,
+    /// Summed from creation to close of each span, so includes waiting
+    /// and nested spans, and exceeds wall time for concurrent spans.
+    pub time: Duration,
+}
+
+/// Starts totaling the spans created from now on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Totals so far, by span name.
+#[must_use]
+pub fn totals() -> BTreeMap<&'static str, Total> {
+    TOTALS
+        .get()
+        .map(|totals| {
+            totals.lock().unwrap_or_else(|e| e.into_inner()).clone()
+        })
+        .unwrap_or_default()
+}
+
+struct Created(Instant);
+
+/// Does nothing until [`enable`]d.
+pub struct Layer;
+
+impl<S> tracing_subscriber::Layer<S> for Layer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        _attrs: &span::Attributes<'_>,
+        id: &span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Created(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(Created(created)) = span.extensions_mut().remove() else {
+            return;
+        };
+        let mut totals = TOTALS
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let total = totals.entry(span.name()).or_default();
+        total.count += 1;
+        total.time += created.elapsed();
+    }
+}